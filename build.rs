@@ -0,0 +1,32 @@
+use std::process::Command;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+fn git(args: &[&str]) -> String {
+    Command::new("git")
+        .args(args)
+        .output()
+        .ok()
+        .filter(|output| output.status.success())
+        .and_then(|output| String::from_utf8(output.stdout).ok())
+        .map(|s| s.trim().to_string())
+        .filter(|s| !s.is_empty())
+        .unwrap_or_else(|| "unknown".to_string())
+}
+
+fn main() {
+    let build_time = SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .map(|d| d.as_millis())
+        .unwrap_or_default();
+
+    println!("cargo:rustc-env=RUSTLINK_BUILD_TIME={}", build_time);
+    println!("cargo:rustc-env=RUSTLINK_GIT_COMMIT={}", git(&["rev-parse", "HEAD"]));
+    println!("cargo:rustc-env=RUSTLINK_GIT_BRANCH={}", git(&["rev-parse", "--abbrev-ref", "HEAD"]));
+    println!("cargo:rustc-env=RUSTLINK_GIT_COMMIT_TIME={}", git(&["log", "-1", "--format=%ct"]));
+
+    // Sources too, so the build time follows every rebuild and not just git state changes
+    println!("cargo:rerun-if-changed=src");
+    println!("cargo:rerun-if-changed=Cargo.toml");
+    println!("cargo:rerun-if-changed=.git/HEAD");
+    println!("cargo:rerun-if-changed=.git/refs");
+}
@@ -0,0 +1,35 @@
+use crate::types::stats::RustlinkMock;
+use crate::utils::{reload_config, send_error_response, send_response};
+use axum::{
+    extract::State,
+    http::{HeaderMap, StatusCode},
+    response::Response,
+};
+use serde_json::json;
+use std::sync::{Arc, RwLock};
+
+/// `POST /v4/admin/reload`: same as sending SIGHUP to the process.
+pub async fn handler_reload(
+    State(rustlink): State<Arc<RwLock<RustlinkMock>>>,
+    headers: HeaderMap,
+) -> Response {
+    match reload_config(&rustlink).await {
+        Ok(requires_restart) => send_response(
+            &headers,
+            Some(json!({
+                "reloaded": true,
+                "requiresRestart": requires_restart
+            })),
+            StatusCode::OK,
+            false,
+        ),
+        Err(e) => send_error_response(
+            &headers,
+            StatusCode::BAD_REQUEST,
+            "Bad Request",
+            &e,
+            "/v4/admin/reload",
+            false,
+        ),
+    }
+}
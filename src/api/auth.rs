@@ -0,0 +1,53 @@
+use crate::types::stats::RustlinkMock;
+use crate::utils::{logger, send_error_response};
+use axum::{
+    extract::{Request, State},
+    http::{StatusCode, header::AUTHORIZATION},
+    middleware::Next,
+    response::Response,
+};
+use std::sync::{Arc, RwLock};
+
+/// Rejects requests whose `Authorization` header doesn't match one of the configured
+/// passwords. Runs before the WebSocket upgrade, so unauthenticated upgrades are refused too.
+pub async fn middleware(
+    State(rustlink): State<Arc<RwLock<RustlinkMock>>>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let path = request.uri().path().to_string();
+    let password = request
+        .headers()
+        .get(AUTHORIZATION)
+        .and_then(|v| v.to_str().ok());
+
+    let authorized = {
+        let rustlink_read = rustlink.read().unwrap();
+        let server = &rustlink_read.config.server;
+        (path == "/version" && server.public_version)
+            || password.is_some_and(|password| server.passwords.iter().any(|p| p == password))
+    };
+    if authorized {
+        return next.run(request).await;
+    }
+
+    let message = if password.is_none() {
+        "Authorization header is missing."
+    } else {
+        "Invalid password provided."
+    };
+    logger(
+        "warn",
+        "Auth",
+        &format!("Rejected request to {}: {}", path, message),
+        None,
+    );
+    send_error_response(
+        request.headers(),
+        StatusCode::UNAUTHORIZED,
+        "Unauthorized",
+        message,
+        &path,
+        false,
+    )
+}
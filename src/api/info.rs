@@ -1,33 +1,59 @@
 use axum::{
+    extract::State,
     http::{HeaderMap, StatusCode},
     response::Response,
 };
-use crate::utils::{get_version, send_response};
+use crate::playback::filters;
+use crate::types::audio_engine::AudioEngineCommand;
+use crate::types::stats::RustlinkMock;
+use crate::utils::{get_version_object, send_response};
 use serde_json::json;
+use std::sync::{Arc, RwLock};
+use tokio::sync::mpsc;
+
+pub async fn handler(
+    State(rustlink): State<Arc<RwLock<RustlinkMock>>>,
+    headers: HeaderMap,
+) -> Response {
+    let version = get_version_object();
+
+    let audio_engine = rustlink.read().unwrap().audio_engine.clone();
+    let (tx, mut rx) = mpsc::channel(1);
+    let source_managers = if audio_engine.send(AudioEngineCommand::GetSources { resp: tx }).await.is_ok() {
+        rx.recv().await.unwrap_or_default()
+    } else {
+        Vec::new()
+    };
+
+    let build_time: i64 = env!("RUSTLINK_BUILD_TIME").parse().unwrap_or(-1);
+    // Lavalink reports the commit time in milliseconds
+    let commit_time = env!("RUSTLINK_GIT_COMMIT_TIME")
+        .parse::<i64>()
+        .map(|t| t * 1000)
+        .unwrap_or(-1);
 
-pub async fn handler(headers: HeaderMap) -> Response {
-    let version_str = get_version(false).to_string();
-    
     let response = json!({
         "version": {
-            "semver": version_str,
-            "major": 0,
-            "minor": 1,
-            "patch": 0,
-            "preRelease": null
+            "semver": version.to_string(),
+            "major": version.major,
+            "minor": version.minor,
+            "patch": version.patch,
+            "preRelease": version.pre,
+            "build": null
         },
-        "buildTime": -1,
+        "buildTime": build_time,
         "git": {
-            "commit": "unknown",
-            "branch": "unknown"
+            "branch": env!("RUSTLINK_GIT_BRANCH"),
+            "commit": env!("RUSTLINK_GIT_COMMIT"),
+            "commitTime": commit_time
         },
         "node": "rust", 
         "voice": {
             "name": "rustlink-voice",
             "version": "0.1.0"
         },
-        "sourceManagers": [],
-        "filters": [],
+        "sourceManagers": source_managers,
+        "filters": filters::names(),
         "plugins": []
     });
 
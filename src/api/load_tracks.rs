@@ -0,0 +1,60 @@
+use axum::{
+    extract::{Query, State},
+    http::{HeaderMap, StatusCode},
+    response::Response,
+};
+use crate::types::audio_engine::AudioEngineCommand;
+use crate::types::stats::RustlinkMock;
+use crate::utils::{send_error_response, send_response};
+use serde::Deserialize;
+use std::sync::{Arc, RwLock};
+use tokio::sync::mpsc;
+
+const PATH: &str = "/v4/loadtracks";
+
+#[derive(Deserialize)]
+pub struct LoadTracksQuery {
+    identifier: Option<String>,
+}
+
+pub async fn handler(
+    State(rustlink): State<Arc<RwLock<RustlinkMock>>>,
+    headers: HeaderMap,
+    Query(params): Query<LoadTracksQuery>,
+) -> Response {
+    let identifier = match params.identifier.filter(|i| !i.trim().is_empty()) {
+        Some(identifier) => identifier,
+        None => {
+            return send_error_response(
+                &headers,
+                StatusCode::BAD_REQUEST,
+                "Bad Request",
+                "Missing identifier query parameter.",
+                PATH,
+                false,
+            );
+        }
+    };
+
+    let audio_engine = rustlink.read().unwrap().audio_engine.clone();
+    let (tx, mut rx) = mpsc::channel(1);
+    let command = AudioEngineCommand::LoadTracks {
+        identifier,
+        resp: tx,
+    };
+
+    if audio_engine.send(command).await.is_ok()
+        && let Some(result) = rx.recv().await
+    {
+        return send_response(&headers, Some(result), StatusCode::OK, false);
+    }
+
+    send_error_response(
+        &headers,
+        StatusCode::INTERNAL_SERVER_ERROR,
+        "Internal Server Error",
+        "No response from AudioEngine.",
+        PATH,
+        false,
+    )
+}
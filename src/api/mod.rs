@@ -1,17 +1,27 @@
+pub mod admin;
+pub mod auth;
 pub mod decode_track;
 pub mod decode_tracks;
 pub mod encode_track;
 pub mod info;
+pub mod load_tracks;
 pub mod players;
+pub mod protection;
 pub mod sessions;
 pub mod stats;
 pub mod version;
+pub mod websocket;
 
+pub use admin::handler_reload as handler_admin_reload;
+pub use auth::middleware as auth_middleware;
 pub use decode_track::handler as handler_decode_track;
 pub use decode_tracks::handler as handler_decode_tracks;
 pub use encode_track::handler as handler_encode_track;
 pub use info::handler as handler_info;
+pub use load_tracks::handler as handler_load_tracks;
 pub use players::handler as handler_players;
+pub use protection::middleware as protection_middleware;
 pub use sessions::handler as handler_sessions;
 pub use stats::handler as handler_stats;
 pub use version::handler as handler_version;
+pub use websocket::handler as handler_websocket;
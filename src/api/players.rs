@@ -1,21 +1,37 @@
+use crate::types::audio_engine::AudioEngineCommand;
 use crate::types::stats::RustlinkMock;
 use crate::utils::{decode_track, send_response};
 use crate::managers::player_manager::PlayerManager;
 use axum::{
     Json,
-    extract::{State, Path},
+    extract::{Query, State},
     http::{HeaderMap, Method, StatusCode, Uri},
     response::{Response, IntoResponse},
 };
 use chrono::Utc;
+use serde::Deserialize;
 use serde_json::{json, Value};
 use std::sync::{Arc, RwLock};
+use tokio::sync::mpsc;
+
+#[derive(Deserialize)]
+pub struct PlayerQuery {
+    #[serde(rename = "noReplace")]
+    no_replace: Option<bool>,
+}
+
+enum TrackUpdate {
+    Keep,
+    Stop,
+    Play { encoded: String, info: Value },
+}
 
 pub async fn handler(
     state: State<Arc<RwLock<RustlinkMock>>>,
     method: Method,
     headers: HeaderMap,
     uri: Uri,
+    Query(query): Query<PlayerQuery>,
     body: Option<Json<Value>>,
 ) -> Response {
     let path = uri.path();
@@ -24,13 +40,18 @@ pub async fn handler(
     let session_id = parts.get(3).copied();
     let guild_id: Option<String> = parts.get(5).map(|s| s.to_string());
 
-    let player_manager = {
+    let session = {
         let rustlink_read = state.read().unwrap();
-        session_id.and_then(|sid| rustlink_read.sessions.get(sid).map(|s| s.player_manager.clone()))
+        session_id.and_then(|sid| {
+            rustlink_read
+                .sessions
+                .get(sid)
+                .map(|s| (s.player_manager.clone(), s.user_id.clone().unwrap_or_default()))
+        })
     };
 
-    let player_manager = match player_manager {
-        Some(pm) => pm,
+    let (player_manager, user_id) = match session {
+        Some(session) => session,
         None => {
             return send_response(
                 &headers,
@@ -67,10 +88,12 @@ pub async fn handler(
         }
         (Some(gid), Method::PATCH) => {
             if let Some(Json(payload)) = body {
-                // Process PATCH
-                let _ = player_manager.set_filters(gid.clone(), payload).await;
-                let player = player_manager.get_player(gid).await.unwrap_or(Value::Null);
-                send_response(&headers, Some(player), StatusCode::OK, false).into_response()
+                let audio_engine = state.read().unwrap().audio_engine.clone();
+                let no_replace = query.no_replace.unwrap_or(false);
+                match update_player(&audio_engine, &player_manager, &user_id, gid, &payload, no_replace).await {
+                    Ok(player) => send_response(&headers, Some(player), StatusCode::OK, false).into_response(),
+                    Err((status, message)) => send_error(&headers, &message, status, path).into_response(),
+                }
             } else {
                 send_error(&headers, "Missing body", StatusCode::BAD_REQUEST, path).into_response()
             }
@@ -79,6 +102,187 @@ pub async fn handler(
     }
 }
 
+/// Applies a Lavalink player update payload, creating the player if it doesn't exist yet.
+async fn update_player(
+    audio_engine: &mpsc::Sender<AudioEngineCommand>,
+    player_manager: &PlayerManager,
+    user_id: &str,
+    guild_id: String,
+    payload: &Value,
+    no_replace: bool,
+) -> Result<Value, (StatusCode, String)> {
+    let bad_request = |message: &str| (StatusCode::BAD_REQUEST, message.to_string());
+
+    let track = payload.get("track");
+    // `encodedTrack` and `identifier` are the deprecated top-level forms of `track.*`.
+    let encoded = track
+        .and_then(|t| t.get("encoded"))
+        .or_else(|| payload.get("encodedTrack"));
+    let identifier = track
+        .and_then(|t| t.get("identifier"))
+        .or_else(|| payload.get("identifier"));
+    let user_data = track.and_then(|t| t.get("userData"));
+
+    if encoded.is_some() && identifier.is_some() {
+        return Err(bad_request("Cannot specify both track.encoded and track.identifier"));
+    }
+    if user_data.is_some_and(|u| !u.is_object()) {
+        return Err(bad_request("track.userData must be an object"));
+    }
+
+    let position = match payload.get("position") {
+        None => None,
+        Some(v) => Some(v.as_i64().filter(|p| *p >= 0).ok_or_else(|| bad_request("position must be a non-negative integer"))?),
+    };
+    let end_time = match payload.get("endTime") {
+        None => None,
+        Some(Value::Null) => Some(None),
+        Some(v) => Some(Some(v.as_i64().filter(|t| *t > 0).ok_or_else(|| bad_request("endTime must be a positive integer or null"))?)),
+    };
+    let volume = match payload.get("volume") {
+        None => None,
+        Some(v) => Some(v.as_u64().filter(|v| *v <= 1000).ok_or_else(|| bad_request("volume must be between 0 and 1000"))?),
+    };
+    let paused = match payload.get("paused") {
+        None => None,
+        Some(v) => Some(v.as_bool().ok_or_else(|| bad_request("paused must be a boolean"))?),
+    };
+    let voice = match payload.get("voice") {
+        None => None,
+        Some(v) => {
+            let field = |key: &str| v.get(key).and_then(|s| s.as_str()).map(|s| s.to_string());
+            match (field("sessionId"), field("token"), field("endpoint")) {
+                (Some(session_id), Some(token), Some(endpoint)) => Some((session_id, token, endpoint)),
+                _ => return Err(bad_request("Partial voice state, sessionId, token and endpoint are required")),
+            }
+        }
+    };
+
+    let track_update = match (encoded, identifier) {
+        (Some(Value::Null), _) => TrackUpdate::Stop,
+        (Some(Value::String(encoded)), _) => match decode_track(encoded) {
+            Ok(decoded) => TrackUpdate::Play {
+                encoded: decoded.encoded,
+                info: json!(decoded.info),
+            },
+            Err(_) => return Err(bad_request("Invalid encoded track")),
+        },
+        (Some(_), _) => return Err(bad_request("track.encoded must be a string or null")),
+        (None, Some(Value::String(identifier))) => load_track(audio_engine, identifier).await?,
+        (None, Some(_)) => return Err(bad_request("track.identifier must be a string")),
+        (None, None) => TrackUpdate::Keep,
+    };
+
+    let internal_error = |e: String| (StatusCode::INTERNAL_SERVER_ERROR, e);
+
+    if player_manager.get_player(guild_id.clone()).await.is_none() {
+        player_manager
+            .create(guild_id.clone(), user_id.to_string(), None)
+            .await
+            .map_err(internal_error)?;
+    }
+
+    if let Some((session_id, token, endpoint)) = voice {
+        player_manager
+            .set_voice(guild_id.clone(), session_id, token, endpoint)
+            .await
+            .map_err(internal_error)?;
+    }
+
+    // Whether `position` and `endTime` should be applied: to the new track if it started,
+    // or to the current one when no track change was requested.
+    let apply_timing = match track_update {
+        TrackUpdate::Play { encoded, info } => {
+            let result = player_manager
+                .play(
+                    guild_id.clone(),
+                    json!({
+                        "encoded": encoded,
+                        "info": info,
+                        "userData": user_data.cloned().unwrap_or(json!({})),
+                        "noReplace": no_replace
+                    }),
+                )
+                .await
+                .map_err(internal_error)?;
+            result.as_bool().unwrap_or(false)
+        }
+        TrackUpdate::Stop => {
+            player_manager.stop(guild_id.clone()).await.map_err(internal_error)?;
+            false
+        }
+        TrackUpdate::Keep => {
+            if let Some(user_data) = user_data {
+                player_manager
+                    .set_user_data(guild_id.clone(), user_data.clone())
+                    .await
+                    .map_err(internal_error)?;
+            }
+            true
+        }
+    };
+
+    if apply_timing {
+        if let Some(position) = position {
+            player_manager.seek(guild_id.clone(), position).await.map_err(internal_error)?;
+        }
+        if let Some(end_time) = end_time {
+            player_manager.set_end_time(guild_id.clone(), end_time).await.map_err(internal_error)?;
+        }
+    }
+
+    if let Some(paused) = paused {
+        player_manager.pause(guild_id.clone(), paused).await.map_err(internal_error)?;
+    }
+    if let Some(volume) = volume {
+        player_manager.set_volume(guild_id.clone(), volume).await.map_err(internal_error)?;
+    }
+    if let Some(filters) = payload.get("filters") {
+        player_manager
+            .set_filters(guild_id.clone(), filters.clone())
+            .await
+            .map_err(internal_error)?;
+    }
+
+    Ok(player_manager.get_player(guild_id).await.unwrap_or(Value::Null))
+}
+
+async fn load_track(
+    audio_engine: &mpsc::Sender<AudioEngineCommand>,
+    identifier: &str,
+) -> Result<TrackUpdate, (StatusCode, String)> {
+    let (tx, mut rx) = mpsc::channel(1);
+    let command = AudioEngineCommand::LoadTracks {
+        identifier: identifier.to_string(),
+        resp: tx,
+    };
+    let no_response = || (StatusCode::INTERNAL_SERVER_ERROR, "No response from AudioEngine".to_string());
+    audio_engine.send(command).await.map_err(|_| no_response())?;
+    let result = rx.recv().await.ok_or_else(no_response)?;
+
+    let track = match result["loadType"].as_str() {
+        Some("track") => &result["data"],
+        Some("error") => {
+            let message = result["data"]["message"].as_str().unwrap_or("Failed to load track");
+            return Err((StatusCode::BAD_REQUEST, message.to_string()));
+        }
+        _ => {
+            return Err((
+                StatusCode::BAD_REQUEST,
+                format!("No track found for identifier: {}", identifier),
+            ));
+        }
+    };
+
+    match track["encoded"].as_str() {
+        Some(encoded) => Ok(TrackUpdate::Play {
+            encoded: encoded.to_string(),
+            info: track["info"].clone(),
+        }),
+        None => Err((StatusCode::BAD_REQUEST, "Loaded track has no encoded data".to_string())),
+    }
+}
+
 fn send_error(headers: &HeaderMap, message: &str, status: StatusCode, path: &str) -> Response {
     send_response(
         headers,
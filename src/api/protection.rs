@@ -0,0 +1,59 @@
+use crate::types::stats::RustlinkMock;
+use crate::utils::{logger, send_error_response};
+use axum::{
+    extract::{ConnectInfo, Request, State},
+    http::StatusCode,
+    middleware::Next,
+    response::Response,
+};
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+
+/// Refuses requests from addresses that are blocked by DoS protection or over the rate
+/// limit. Runs before authentication, so failed password attempts count as well.
+pub async fn middleware(
+    State(rustlink): State<Arc<RwLock<RustlinkMock>>>,
+    ConnectInfo(remote_address): ConnectInfo<SocketAddr>,
+    request: Request,
+    next: Next,
+) -> Response {
+    let path = request.uri().path().to_string();
+    match check(&rustlink, &remote_address) {
+        Ok(()) => next.run(request).await,
+        Err((status, error, message)) => {
+            logger(
+                "warn",
+                "Protection",
+                &format!("Rejected request to {} from {}: {}", path, remote_address.ip(), message),
+                None,
+            );
+            send_error_response(request.headers(), status, error, message, &path, false)
+        }
+    }
+}
+
+/// Counts one request from `remote_address` against DoS protection and the rate limit.
+/// Also called for messages received over WebSocket connections.
+pub fn check(
+    rustlink: &Arc<RwLock<RustlinkMock>>,
+    remote_address: &SocketAddr,
+) -> Result<(), (StatusCode, &'static str, &'static str)> {
+    let ip = remote_address.ip().to_string();
+    let mut rustlink_write = rustlink.write().unwrap();
+
+    if rustlink_write.dos_protection.check(&ip).is_err() {
+        return Err((
+            StatusCode::FORBIDDEN,
+            "Forbidden",
+            "Too many requests, this address is temporarily blocked.",
+        ));
+    }
+    if !rustlink_write.rate_limit.check(&ip) {
+        return Err((
+            StatusCode::TOO_MANY_REQUESTS,
+            "Too Many Requests",
+            "Rate limit exceeded.",
+        ));
+    }
+    Ok(())
+}
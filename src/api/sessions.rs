@@ -9,7 +9,6 @@ use crate::types::stats::RustlinkMock;
 use std::sync::{Arc, RwLock};
 use serde::Deserialize;
 use serde_json::json;
-use chrono::Utc;
 
 #[derive(Deserialize)]
 pub struct SessionPatch {
@@ -26,20 +25,18 @@ pub async fn handler(
     let mut rustlink_write = rustlink.write().unwrap();
 
     if let Some(session) = rustlink_write.sessions.get_mut(&session_id) {
-        // Since Session struct in stats.rs might not have resuming/timeout fields yet, 
-        // we might need to update the struct definition first.
-        // For now, we acknowledge the request but don't persist if fields are missing.
-        // Checking rustlink/src/types/stats.rs context...
-        // It has `Session { players: SessionPlayers }`. 
-        // It does NOT have resuming/timeout.
-        
-        // TODO: Update Session struct to support resuming/timeout.
-        
+        if let Some(resuming) = payload.resuming {
+            session.resuming = resuming;
+        }
+        if let Some(timeout) = payload.timeout {
+            session.timeout = timeout;
+        }
+
         let response = json!({
-            "resuming": payload.resuming.unwrap_or(false),
-            "timeout": payload.timeout.unwrap_or(0)
+            "resuming": session.resuming,
+            "timeout": session.timeout
         });
-        
+
         return send_response(&headers, Some(response), StatusCode::OK, false);
     }
 
@@ -0,0 +1,305 @@
+use crate::managers::player_manager::PlayerManager;
+use super::protection;
+use crate::types::stats::RustlinkMock;
+use crate::utils::{decode_track, logger, parse_client, send_error_response, verify_discord_id};
+use axum::{
+    extract::{
+        ConnectInfo, State,
+        ws::{Message, WebSocket, WebSocketUpgrade},
+    },
+    http::{HeaderMap, StatusCode},
+    response::Response,
+};
+use serde_json::{json, Value};
+use std::net::SocketAddr;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+use tokio::sync::mpsc;
+
+const PATH: &str = "/v4/websocket";
+
+pub async fn handler(
+    ws: WebSocketUpgrade,
+    State(rustlink): State<Arc<RwLock<RustlinkMock>>>,
+    ConnectInfo(remote_address): ConnectInfo<SocketAddr>,
+    headers: HeaderMap,
+) -> Response {
+    let header_str = |name: &str| headers.get(name).and_then(|v| v.to_str().ok());
+
+    let user_id = match header_str("user-id") {
+        Some(id) if verify_discord_id(id) => id.to_string(),
+        _ => {
+            return send_error_response(
+                &headers,
+                StatusCode::BAD_REQUEST,
+                "Bad Request",
+                "Invalid or missing User-Id header.",
+                PATH,
+                false,
+            );
+        }
+    };
+
+    let client_info = match parse_client(header_str("client-name")) {
+        Some(info) => info,
+        None => {
+            return send_error_response(
+                &headers,
+                StatusCode::BAD_REQUEST,
+                "Bad Request",
+                "Invalid or missing Client-Name header.",
+                PATH,
+                false,
+            );
+        }
+    };
+
+    let client_name = client_info.name.clone();
+    let client_info = serde_json::to_value(&client_info).ok();
+    let resume_session_id = header_str("session-id").map(|s| s.to_string());
+
+    ws.on_upgrade(move |socket| {
+        handle_socket(socket, rustlink, remote_address, user_id, client_name, client_info, resume_session_id)
+    })
+}
+
+async fn handle_socket(
+    mut socket: WebSocket,
+    rustlink: Arc<RwLock<RustlinkMock>>,
+    remote_address: SocketAddr,
+    user_id: String,
+    client_name: String,
+    client_info: Option<Value>,
+    resume_session_id: Option<String>,
+) {
+    let (tx, mut rx) = mpsc::unbounded_channel();
+
+    let (session_id, resumed) = {
+        let mut rustlink_write = rustlink.write().unwrap();
+        match resume_session_id {
+            Some(id) if rustlink_write.sessions.resume(&id, &user_id, tx.clone()) => {
+                drop(tx);
+                (id, true)
+            }
+            _ => (rustlink_write.sessions.create(user_id.clone(), client_info, tx), false),
+        }
+    };
+
+    logger(
+        "info",
+        "WebSocket",
+        &format!(
+            "Connection established with {} ({}), session {}{}",
+            client_name,
+            user_id,
+            session_id,
+            if resumed { " (resumed)" } else { "" }
+        ),
+        None,
+    );
+
+    // The session holds the only sender, so deleting it ends the writer below.
+    let mut unsent = Vec::new();
+    loop {
+        tokio::select! {
+            outgoing = rx.recv() => match outgoing {
+                Some(payload) => {
+                    if socket.send(Message::Text(payload.to_string().into())).await.is_err() {
+                        unsent.push(payload);
+                        break;
+                    }
+                }
+                None => break,
+            },
+            incoming = socket.recv() => match incoming {
+                Some(Ok(Message::Text(text))) => match protection::check(&rustlink, &remote_address) {
+                    Ok(()) => handle_message(&rustlink, &session_id, &text).await,
+                    // A blocked address loses the connection, one over the rate limit the message
+                    Err((status, _, message)) => {
+                        logger(
+                            "warn",
+                            "WebSocket",
+                            &format!("Dropped message from session {}: {}", session_id, message),
+                            None,
+                        );
+                        if status == StatusCode::FORBIDDEN {
+                            break;
+                        }
+                    }
+                },
+                Some(Ok(Message::Close(_))) | Some(Err(_)) | None => break,
+                Some(Ok(_)) => {}
+            },
+        }
+    }
+
+    // Later sends fail and get queued by the session, so these go back ahead of them
+    rx.close();
+    while let Ok(payload) = rx.try_recv() {
+        unsent.push(payload);
+    }
+
+    let resume_window = {
+        let mut rustlink_write = rustlink.write().unwrap();
+        match rustlink_write.sessions.get_mut(&session_id) {
+            Some(session) if session.resuming => Some((session.disconnect(unsent), session.timeout)),
+            _ => None,
+        }
+    };
+
+    match resume_window {
+        Some((disconnected_at, timeout)) => {
+            logger(
+                "info",
+                "WebSocket",
+                &format!(
+                    "Connection closed for session {}, it can be resumed within {}s",
+                    session_id, timeout
+                ),
+                None,
+            );
+            tokio::spawn(async move {
+                tokio::time::sleep(Duration::from_secs(timeout)).await;
+                let expired = {
+                    let rustlink_read = rustlink.read().unwrap();
+                    rustlink_read
+                        .sessions
+                        .get(&session_id)
+                        .is_some_and(|s| s.disconnected_at() == Some(disconnected_at))
+                };
+                if expired {
+                    logger(
+                        "info",
+                        "WebSocket",
+                        &format!("Session {} was not resumed in time", session_id),
+                        None,
+                    );
+                    destroy_session(&rustlink, &session_id).await;
+                }
+            });
+        }
+        None => {
+            logger(
+                "info",
+                "WebSocket",
+                &format!("Connection closed for session {}", session_id),
+                None,
+            );
+            destroy_session(&rustlink, &session_id).await;
+        }
+    }
+}
+
+async fn destroy_session(rustlink: &Arc<RwLock<RustlinkMock>>, session_id: &str) {
+    let session = rustlink.write().unwrap().sessions.delete(session_id);
+    if let Some(session) = session {
+        let _ = session.player_manager.destroy_all().await;
+    }
+}
+
+async fn handle_message(rustlink: &Arc<RwLock<RustlinkMock>>, session_id: &str, text: &str) {
+    let payload: Value = match serde_json::from_str(text) {
+        Ok(v) => v,
+        Err(_) => {
+            logger("warn", "WebSocket", &format!("Received invalid JSON from session {}", session_id), None);
+            return;
+        }
+    };
+
+    let (player_manager, user_id) = {
+        let rustlink_read = rustlink.read().unwrap();
+        match rustlink_read.sessions.get(session_id) {
+            Some(session) => (
+                session.player_manager.clone(),
+                session.user_id.clone().unwrap_or_default(),
+            ),
+            None => return,
+        }
+    };
+
+    let op = payload["op"].as_str().unwrap_or("");
+    let guild_id = match payload["guildId"].as_str() {
+        Some(id) => id.to_string(),
+        None => {
+            logger("warn", "WebSocket", &format!("Op \"{}\" is missing guildId", op), None);
+            return;
+        }
+    };
+
+    match op {
+        "voiceUpdate" => {
+            ensure_player(&player_manager, &guild_id, &user_id).await;
+            let event = &payload["event"];
+            let _ = player_manager
+                .set_voice(
+                    guild_id,
+                    payload["sessionId"].as_str().unwrap_or("").to_string(),
+                    event["token"].as_str().unwrap_or("").to_string(),
+                    event["endpoint"].as_str().unwrap_or("").to_string(),
+                )
+                .await;
+        }
+        "play" => {
+            let encoded = payload["track"].as_str().unwrap_or("");
+            let decoded = match decode_track(encoded) {
+                Ok(decoded) => decoded,
+                Err(e) => {
+                    logger("warn", "WebSocket", &format!("Failed to decode track: {}", e), None);
+                    return;
+                }
+            };
+            ensure_player(&player_manager, &guild_id, &user_id).await;
+            let _ = player_manager
+                .play(
+                    guild_id.clone(),
+                    json!({
+                        "encoded": decoded.encoded,
+                        "info": decoded.info,
+                        "noReplace": payload["noReplace"].as_bool().unwrap_or(false)
+                    }),
+                )
+                .await;
+            if let Some(volume) = payload["volume"].as_u64() {
+                let _ = player_manager.set_volume(guild_id.clone(), volume).await;
+            }
+            if let Some(start_time) = payload["startTime"].as_i64() {
+                let _ = player_manager.seek(guild_id.clone(), start_time).await;
+            }
+            if let Some(pause) = payload["pause"].as_bool() {
+                let _ = player_manager.pause(guild_id, pause).await;
+            }
+        }
+        "stop" => {
+            let _ = player_manager.stop(guild_id).await;
+        }
+        "pause" => {
+            let pause = payload["pause"].as_bool().unwrap_or(true);
+            let _ = player_manager.pause(guild_id, pause).await;
+        }
+        "seek" => {
+            let position = payload["position"].as_i64().unwrap_or(0);
+            let _ = player_manager.seek(guild_id, position).await;
+        }
+        "volume" => {
+            let volume = payload["volume"].as_u64().unwrap_or(100);
+            let _ = player_manager.set_volume(guild_id, volume).await;
+        }
+        "filters" => {
+            let _ = player_manager.set_filters(guild_id, payload).await;
+        }
+        "destroy" => {
+            let _ = player_manager.destroy(guild_id).await;
+        }
+        _ => {
+            logger("warn", "WebSocket", &format!("Unknown op \"{}\" from session {}", op, session_id), None);
+        }
+    }
+}
+
+async fn ensure_player(player_manager: &PlayerManager, guild_id: &str, user_id: &str) {
+    if player_manager.get_player(guild_id.to_string()).await.is_none() {
+        let _ = player_manager
+            .create(guild_id.to_string(), user_id.to_string(), None)
+            .await;
+    }
+}
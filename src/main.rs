@@ -1,10 +1,13 @@
 use axum::{
     Router,
+    middleware,
     routing::{get, patch, post},
 };
-use std::net::SocketAddr;
+use std::net::{IpAddr, SocketAddr};
 use std::sync::{Arc, RwLock};
 use tokio::net::TcpListener;
+#[cfg(unix)]
+use tokio::signal::unix::{SignalKind, signal};
 use tokio::sync::mpsc;
 
 mod api;
@@ -13,11 +16,17 @@ mod playback;
 mod sources;
 mod types;
 mod utils;
+mod voice;
 
+use crate::managers::dos_protection_manager::DosProtectionManager;
+use crate::managers::rate_limit_manager::RateLimitManager;
 use crate::managers::session_manager::SessionManager;
+use crate::managers::update_manager::UpdateManager;
 use crate::playback::audio_engine::AudioEngine;
 use crate::types::stats::{RustlinkMock, RustlinkStats};
 
+const DEFAULT_CONFIG_PATH: &str = "config.json";
+
 #[tokio::main]
 async fn main() {
     let version = utils::get_version(false);
@@ -36,9 +45,20 @@ async fn main() {
 
     println!("\x1b[32m{}\x1b[0m", ascii);
 
+    let config_path = std::env::var("RUSTLINK_CONFIG").unwrap_or_else(|_| DEFAULT_CONFIG_PATH.to_string());
+    let config = match utils::load_config(&config_path) {
+        Ok(config) => config,
+        Err(e) => {
+            utils::logger("error", "Config", &format!("Invalid configuration: {}", e), None);
+            std::process::exit(1);
+        }
+    };
+    utils::init_logger(&config.logging);
+
     // Initialize Audio Engine (Successor to Worker)
     let (tx, rx) = mpsc::channel(100);
-    let mut audio_engine = AudioEngine::new(rx);
+    let (event_tx, mut event_rx) = mpsc::unbounded_channel();
+    let mut audio_engine = AudioEngine::new(rx, event_tx, &config);
     tokio::spawn(async move {
         audio_engine.run().await;
     });
@@ -47,17 +67,55 @@ async fn main() {
         statistics: RustlinkStats {
             players: 0,
             playing_players: 0,
+            frame_stats: None,
         },
-        sessions: SessionManager::new(tx),
+        sessions: SessionManager::new(tx.clone()),
+        audio_engine: tx,
+        dos_protection: DosProtectionManager::new(&config),
+        rate_limit: RateLimitManager::new(&config),
+        config: config.clone(),
+        config_path,
     }));
 
+    // Forward player events to the WebSocket of the session that owns the player
+    let event_state = rustlink_state.clone();
+    tokio::spawn(async move {
+        while let Some(event) = event_rx.recv().await {
+            let mut rustlink = event_state.write().unwrap();
+            if let Some(session) = rustlink.sessions.get_mut(&event.session_id) {
+                session.send(event.payload);
+            }
+        }
+    });
+
+    UpdateManager::new(rustlink_state.clone()).start();
+
+    #[cfg(unix)]
+    {
+        let reload_state = rustlink_state.clone();
+        tokio::spawn(async move {
+            let Ok(mut hangup) = signal(SignalKind::hangup()) else {
+                utils::logger("warn", "Config", "Could not listen for SIGHUP, use /v4/admin/reload instead", None);
+                return;
+            };
+            while hangup.recv().await.is_some() {
+                if let Err(e) = utils::reload_config(&reload_state).await {
+                    utils::logger("error", "Config", &format!("Reload failed, keeping the current configuration: {}", e), None);
+                }
+            }
+        });
+    }
+
     let app = Router::new()
         .route("/v4/decodetrack", get(api::handler_decode_track))
         .route("/v4/decodetracks", post(api::handler_decode_tracks))
         .route("/v4/encodetrack", get(api::handler_encode_track))
         .route("/v4/info", get(api::handler_info))
+        .route("/v4/loadtracks", get(api::handler_load_tracks))
         .route("/v4/stats", get(api::handler_stats))
         .route("/version", get(api::handler_version))
+        .route("/v4/admin/reload", post(api::handler_admin_reload))
+        .route("/v4/websocket", get(api::handler_websocket))
         .route("/v4/sessions/{sessionId}", patch(api::handler_sessions))
         .route(
             "/v4/sessions/{sessionId}/players",
@@ -69,11 +127,23 @@ async fn main() {
                 .patch(api::handler_players)
                 .delete(api::handler_players),
         )
+        .layer(middleware::from_fn_with_state(
+            rustlink_state.clone(),
+            api::auth_middleware,
+        ))
+        .layer(middleware::from_fn_with_state(
+            rustlink_state.clone(),
+            api::protection_middleware,
+        ))
         .with_state(rustlink_state);
 
-    let addr = SocketAddr::from(([0, 0, 0, 0], 8080));
+    // The host is validated when the config is loaded
+    let host: IpAddr = config.server.host.parse().unwrap();
+    let addr = SocketAddr::from((host, config.server.port));
     println!("Listening on {}", addr);
 
     let listener = TcpListener::bind(addr).await.unwrap();
-    axum::serve(listener, app).await.unwrap();
+    axum::serve(listener, app.into_make_service_with_connect_info::<SocketAddr>())
+        .await
+        .unwrap();
 }
@@ -2,6 +2,7 @@ use serde_json::json;
 use std::time::{Duration, Instant};
 use tokio::time::interval;
 
+use crate::types::config::Config;
 use crate::types::stats::RustlinkMock;
 // use crate::utils::make_request::make_request; // Stubbed for now
 
@@ -16,15 +17,19 @@ pub struct ConnectionManager {
 }
 
 impl ConnectionManager {
-    pub fn new() -> Self {
+    pub fn new(config: &Config) -> Self {
         Self {
-            interval_ms: 300000,
+            interval_ms: config.connection.interval_ms,
             status: "unknown".to_string(),
             metrics: json!({}),
             is_checking: false,
         }
     }
 
+    pub fn reload(&mut self, config: &Config) {
+        self.interval_ms = config.connection.interval_ms;
+    }
+
     pub async fn start(&mut self) {
         if self.interval_ms > 0 {
             // In a real implementation, this would spawn a task.
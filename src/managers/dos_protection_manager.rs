@@ -1,6 +1,8 @@
 use std::collections::HashMap;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use crate::types::config::{Config, DosProtectionConfig};
+
 struct IpData {
     count: u32,
     last_reset: u128,
@@ -9,24 +11,35 @@ struct IpData {
 
 pub struct DosProtectionManager {
     ip_request_counts: HashMap<String, IpData>,
-    enabled: bool,
+    config: DosProtectionConfig,
+    last_prune: u128,
 }
 
 impl DosProtectionManager {
-    pub fn new() -> Self {
+    pub fn new(config: &Config) -> Self {
         Self {
             ip_request_counts: HashMap::new(),
-            enabled: false, // Default from config
+            config: config.dos_protection.clone(),
+            last_prune: 0,
         }
     }
 
+    /// Applies new thresholds. Counts and blocks already in place are kept.
+    pub fn reload(&mut self, config: &Config) {
+        self.config = config.dos_protection.clone();
+    }
+
+    /// Counts a request from `remote_address`. Going over `threshold` requests within
+    /// `time_window_ms` blocks the address for `block_duration_ms`.
     pub fn check(&mut self, remote_address: &str) -> Result<(), String> {
-        if !self.enabled {
+        if !self.config.enabled {
             return Ok(());
         }
 
         let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis();
-        
+        let window = self.config.time_window_ms as u128;
+        self.prune(now, window);
+
         let entry = self.ip_request_counts.entry(remote_address.to_string()).or_insert(IpData {
             count: 0,
             last_reset: now,
@@ -37,8 +50,29 @@ impl DosProtectionManager {
             return Err("Forbidden".to_string());
         }
 
-        // Logic ...
-        
+        if now - entry.last_reset >= window {
+            entry.count = 0;
+            entry.last_reset = now;
+        }
+        entry.count += 1;
+
+        if entry.count > self.config.threshold {
+            entry.blocked_until = now + self.config.block_duration_ms as u128;
+            entry.count = 0;
+            entry.last_reset = entry.blocked_until;
+            return Err("Forbidden".to_string());
+        }
+
         Ok(())
     }
+
+    // Forgets addresses that are neither blocked nor counted in the current window
+    fn prune(&mut self, now: u128, window: u128) {
+        if now - self.last_prune < window {
+            return;
+        }
+        self.last_prune = now;
+        self.ip_request_counts
+            .retain(|_, data| now < data.blocked_until || now - data.last_reset < window);
+    }
 }
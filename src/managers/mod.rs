@@ -6,4 +6,5 @@ pub mod rate_limit_manager;
 pub mod route_planner_manager;
 pub mod session_manager;
 pub mod source_manager;
-pub mod stats_manager;
\ No newline at end of file
+pub mod stats_manager;
+pub mod update_manager;
\ No newline at end of file
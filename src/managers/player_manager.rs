@@ -37,6 +37,7 @@ impl PlayerManager {
     pub async fn destroy(&self, guild_id: String) -> Result<Value, String> {
         let (tx, rx) = mpsc::channel(1);
         let command = AudioEngineCommand::DestroyPlayer {
+            session_id: self.session_id.clone(),
             guild_id,
             resp: tx,
         };
@@ -45,6 +46,17 @@ impl PlayerManager {
         rx.recv().await.ok_or("No response from AudioEngine".to_string())
     }
 
+    pub async fn destroy_all(&self) -> Result<Value, String> {
+        let (tx, rx) = mpsc::channel(1);
+        let command = AudioEngineCommand::DestroySessionPlayers {
+            session_id: self.session_id.clone(),
+            resp: tx,
+        };
+        self.sender.send(command).await.map_err(|e| e.to_string())?;
+        let mut rx = rx;
+        rx.recv().await.ok_or("No response from AudioEngine".to_string())
+    }
+
     pub async fn play(&self, guild_id: String, track_payload: Value) -> Result<Value, String> {
         self.send_command(guild_id, "play", vec![track_payload]).await
     }
@@ -64,6 +76,14 @@ impl PlayerManager {
     pub async fn set_volume(&self, guild_id: String, level: u64) -> Result<Value, String> {
         self.send_command(guild_id, "volume", vec![serde_json::Value::Number(level.into())]).await
     }
+
+    pub async fn set_end_time(&self, guild_id: String, end_time: Option<i64>) -> Result<Value, String> {
+        self.send_command(guild_id, "endTime", vec![json!(end_time)]).await
+    }
+
+    pub async fn set_user_data(&self, guild_id: String, user_data: Value) -> Result<Value, String> {
+        self.send_command(guild_id, "userData", vec![user_data]).await
+    }
     
         pub async fn set_filters(&self, guild_id: String, filters: Value) -> Result<Value, String> {
             self.send_command(guild_id, "setFilters", vec![filters]).await
@@ -82,6 +102,8 @@ impl PlayerManager {
 
             let cmd = AudioEngineCommand::PlayerCommand {
 
+                session_id: self.session_id.clone(),
+
                 guild_id,
 
                 command: command.to_string(),
@@ -108,6 +130,8 @@ impl PlayerManager {
 
             let cmd = AudioEngineCommand::GetPlayer {
 
+                session_id: self.session_id.clone(),
+
                 guild_id,
 
                 resp: tx,
@@ -150,6 +174,8 @@ impl PlayerManager {
 
             let cmd = AudioEngineCommand::GetPlayers {
 
+                session_id: self.session_id.clone(),
+
                 resp: tx,
 
             };
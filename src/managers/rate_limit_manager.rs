@@ -1,28 +1,63 @@
 use std::collections::HashMap;
 use std::time::{SystemTime, UNIX_EPOCH};
 
+use crate::types::config::{Config, RateLimitConfig};
+
 struct RateLimitEntry {
     requests: Vec<u128>,
 }
 
 pub struct RateLimitManager {
     store: HashMap<String, RateLimitEntry>,
-    enabled: bool,
+    config: RateLimitConfig,
+    last_prune: u128,
 }
 
 impl RateLimitManager {
-    pub fn new() -> Self {
+    pub fn new(config: &Config) -> Self {
         Self {
             store: HashMap::new(),
-            enabled: false,
+            config: config.rate_limit.clone(),
+            last_prune: 0,
         }
     }
 
-    pub fn check(&mut self, _identifier: &str) -> bool {
-        if !self.enabled {
+    /// Applies new limits. Requests already counted stay in their window.
+    pub fn reload(&mut self, config: &Config) {
+        self.config = config.rate_limit.clone();
+    }
+
+    /// Whether `identifier` may make another request, allowing `max_requests` within any
+    /// `time_window_ms`. Refused requests aren't counted.
+    pub fn check(&mut self, identifier: &str) -> bool {
+        if !self.config.enabled {
             return true;
         }
-        // Logic ...
+
+        let now = SystemTime::now().duration_since(UNIX_EPOCH).unwrap().as_millis();
+        let window = self.config.time_window_ms as u128;
+        self.prune(now, window);
+
+        let entry = self
+            .store
+            .entry(identifier.to_string())
+            .or_insert(RateLimitEntry { requests: Vec::new() });
+        entry.requests.retain(|&time| now - time < window);
+
+        if entry.requests.len() >= self.config.max_requests as usize {
+            return false;
+        }
+        entry.requests.push(now);
         true
     }
+
+    // Forgets identifiers without requests in the current window
+    fn prune(&mut self, now: u128, window: u128) {
+        if now - self.last_prune < window {
+            return;
+        }
+        self.last_prune = now;
+        self.store
+            .retain(|_, entry| entry.requests.last().is_some_and(|&time| now - time < window));
+    }
 }
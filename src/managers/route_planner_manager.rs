@@ -1,18 +1,27 @@
 use std::collections::HashMap;
 
+use crate::types::config::Config;
+
 pub struct RoutePlannerManager {
     ip_blocks: Vec<String>,
     banned_ips: HashMap<String, u128>,
 }
 
 impl RoutePlannerManager {
-    pub fn new() -> Self {
+    pub fn new(config: &Config) -> Self {
         Self {
-            ip_blocks: Vec::new(),
+            ip_blocks: config.route_planner.ip_blocks.clone(),
             banned_ips: HashMap::new(),
         }
     }
 
+    pub fn reload(&mut self, config: &Config) {
+        if self.ip_blocks != config.route_planner.ip_blocks {
+            self.ip_blocks = config.route_planner.ip_blocks.clone();
+            self.banned_ips.clear();
+        }
+    }
+
     pub fn get_ip(&self) -> Option<String> {
         // Stub: Just return None or a dummy IP
         None
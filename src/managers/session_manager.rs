@@ -1,15 +1,77 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
+use std::time::Instant;
 use serde_json::Value;
 use tokio::sync::mpsc;
 
 use crate::types::audio_engine::AudioEngineCommand;
+use crate::utils::generate_random_letters;
 use super::player_manager::PlayerManager;
 
+const SESSION_ID_LENGTH: usize = 16;
+const DEFAULT_RESUME_TIMEOUT: u64 = 60;
+
 pub struct Session {
     pub id: String,
     pub user_id: Option<String>,
     pub client_info: Option<Value>,
     pub player_manager: PlayerManager,
+    pub resuming: bool,
+    /// Seconds a disconnected session is kept alive while `resuming` is enabled.
+    pub timeout: u64,
+    socket: Option<mpsc::UnboundedSender<Value>>,
+    event_queue: VecDeque<Value>,
+    disconnected_at: Option<Instant>,
+}
+
+impl Session {
+    /// Sends a payload to the session's WebSocket. If that fails on a resumable session the
+    /// payload is buffered instead and replayed on resume.
+    pub fn send(&mut self, payload: Value) -> bool {
+        if self.try_send(&payload) {
+            return true;
+        }
+        if self.resuming {
+            self.event_queue.push_back(payload);
+        }
+        false
+    }
+
+    fn try_send(&mut self, payload: &Value) -> bool {
+        if let Some(socket) = &self.socket {
+            if socket.send(payload.clone()).is_ok() {
+                return true;
+            }
+            self.socket = None;
+        }
+        false
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.socket.is_some()
+    }
+
+    /// Detaches the socket and returns the instant used to match the resume timer. `unsent`
+    /// are the payloads the socket took but never delivered; they are replayed first.
+    pub fn disconnect(&mut self, unsent: Vec<Value>) -> Instant {
+        let now = Instant::now();
+        self.socket = None;
+        self.disconnected_at = Some(now);
+        if self.resuming {
+            for payload in unsent.into_iter().rev() {
+                self.event_queue.push_front(payload);
+            }
+        }
+        now
+    }
+
+    pub fn disconnected_at(&self) -> Option<Instant> {
+        self.disconnected_at
+    }
+
+    fn attach(&mut self, socket: mpsc::UnboundedSender<Value>) {
+        self.socket = Some(socket);
+        self.disconnected_at = None;
+    }
 }
 
 pub struct SessionManager {
@@ -25,31 +87,115 @@ impl SessionManager {
         }
     }
 
-    pub fn create(&mut self, _request: &Value, _client_info: Option<Value>) -> String {
-        let session_id = "mock_session_id".to_string(); // In real app generate random
+    pub fn create(
+        &mut self,
+        user_id: String,
+        client_info: Option<Value>,
+        socket: mpsc::UnboundedSender<Value>,
+    ) -> String {
+        let mut session_id = generate_random_letters(SESSION_ID_LENGTH);
+        while self.sessions.contains_key(&session_id) {
+            session_id = generate_random_letters(SESSION_ID_LENGTH);
+        }
         let player_manager = PlayerManager::new(self.audio_engine_sender.clone(), session_id.clone());
-        
-        let session = Session {
+
+        let mut session = Session {
             id: session_id.clone(),
-            user_id: None, // Extract from request
-            client_info: _client_info,
+            user_id: Some(user_id),
+            client_info,
             player_manager,
+            resuming: false,
+            timeout: DEFAULT_RESUME_TIMEOUT,
+            socket: Some(socket),
+            event_queue: VecDeque::new(),
+            disconnected_at: None,
         };
+        session.send(serde_json::json!({
+            "op": "ready",
+            "resumed": false,
+            "sessionId": session_id
+        }));
         self.sessions.insert(session_id.clone(), session);
         session_id
     }
 
+    /// Re-attaches a socket to a disconnected resumable session owned by `user_id`, sends
+    /// `ready` with `resumed: true` and replays every event buffered while it was away.
+    pub fn resume(
+        &mut self,
+        session_id: &str,
+        user_id: &str,
+        socket: mpsc::UnboundedSender<Value>,
+    ) -> bool {
+        let session = match self.sessions.get_mut(session_id) {
+            Some(session)
+                // Not before the old connection has handed over what it didn't deliver
+                if session.resuming
+                    && session.disconnected_at.is_some()
+                    && session.user_id.as_deref() == Some(user_id) =>
+            {
+                session
+            }
+            _ => return false,
+        };
+
+        session.attach(socket);
+        session.try_send(&serde_json::json!({
+            "op": "ready",
+            "resumed": true,
+            "sessionId": session_id
+        }));
+        // Events stay queued until they are sent, so a socket dropping mid-replay loses none
+        while let Some(payload) = session.event_queue.front().cloned() {
+            if !session.try_send(&payload) {
+                break;
+            }
+            session.event_queue.pop_front();
+        }
+        true
+    }
+
     pub fn get(&self, session_id: &str) -> Option<&Session> {
         self.sessions.get(session_id)
     }
-    
+
     pub fn get_mut(&mut self, session_id: &str) -> Option<&mut Session> {
         self.sessions.get_mut(session_id)
     }
 
-    pub fn delete(&mut self, session_id: &str) {
-        // Here we should probably also tell AudioEngine to destroy players for this session.
-        // But for now, just remove session.
-        self.sessions.remove(session_id);
+    /// Removes the session. The caller is responsible for destroying its players through
+    /// the returned session's `player_manager`, since that requires awaiting the AudioEngine.
+    pub fn delete(&mut self, session_id: &str) -> Option<Session> {
+        self.sessions.remove(session_id)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use serde_json::json;
+
+    #[test]
+    fn keeps_what_a_dying_socket_failed_to_send() {
+        let (engine, _engine_rx) = mpsc::channel(1);
+        let mut sessions = SessionManager::new(engine);
+        let (socket, socket_rx) = mpsc::unbounded_channel();
+        let id = sessions.create("1".to_string(), None, socket);
+        let session = sessions.get_mut(&id).unwrap();
+        session.resuming = true;
+
+        // The writer stopped before the session was marked disconnected
+        drop(socket_rx);
+        assert!(!session.send(json!({ "op": "event", "n": 2 })));
+        session.disconnect(vec![json!({ "op": "event", "n": 1 })]);
+        assert!(!session.send(json!({ "op": "event", "n": 3 })));
+
+        assert!(!sessions.resume(&id, "2", mpsc::unbounded_channel().0));
+        let (socket, mut socket_rx) = mpsc::unbounded_channel();
+        assert!(sessions.resume(&id, "1", socket));
+        let received: Vec<Value> = std::iter::from_fn(|| socket_rx.try_recv().ok()).collect();
+        assert_eq!(received[0]["resumed"], true);
+        let replayed: Vec<&Value> = received[1..].iter().map(|payload| &payload["n"]).collect();
+        assert_eq!(replayed, [1, 2, 3]);
     }
-}
\ No newline at end of file
+}
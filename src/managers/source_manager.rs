@@ -1,34 +1,116 @@
 use std::collections::HashMap;
-use serde_json::Value;
-use crate::sources::{Source, youtube::youtube::YoutubeSource, soundcloud::SoundcloudSource, spotify::SpotifySource};
+use serde_json::{json, Value};
+use crate::sources::{
+    Source, bandcamp::BandcampSource, deezer::DeezerSource, google_tts::GoogleTtsSource,
+    http::HttpSource, instagram::InstagramSource, kwai::KwaiSource, lastfm::LastfmSource,
+    local::LocalSource, nicovideo::NicovideoSource, reddit::RedditSource,
+    soundcloud::SoundcloudSource, spotify::SpotifySource, tidal::TidalSource,
+    twitch::TwitchSource, youtube::youtube::YoutubeSource,
+};
+use crate::playback::decoder::StreamInput;
+use crate::types::config::Config;
 use crate::types::http::NodelinkMock;
+use crate::utils::logger;
+
+const DEFAULT_SEARCH_TERM: &str = "ytsearch";
 
 pub struct SourceManager {
     sources: HashMap<String, Box<dyn Source>>,
+    // Search prefix (e.g. "scsearch") -> name of the source that handles it
+    search_terms: HashMap<&'static str, String>,
+    // Names of every known source, registered or disabled
+    known: Vec<&'static str>,
 }
 
 impl SourceManager {
-    pub fn new() -> Self {
-        let mut sources: HashMap<String, Box<dyn Source>> = HashMap::new();
-        
-        sources.insert("youtube".to_string(), Box::new(YoutubeSource::new()));
-        sources.insert("soundcloud".to_string(), Box::new(SoundcloudSource));
-        sources.insert("spotify".to_string(), Box::new(SpotifySource));
-        // Add others...
-
-        Self {
-            sources,
+    pub fn new(config: &Config) -> Self {
+        let mut manager = Self {
+            sources: HashMap::new(),
+            search_terms: HashMap::new(),
+            known: Vec::new(),
+        };
+
+        manager.register("youtube", Box::new(YoutubeSource::new()));
+        manager.register("soundcloud", Box::new(SoundcloudSource));
+        manager.register("spotify", Box::new(SpotifySource));
+        manager.register("deezer", Box::new(DeezerSource));
+        manager.register("bandcamp", Box::new(BandcampSource));
+        manager.register("tidal", Box::new(TidalSource));
+        manager.register("lastfm", Box::new(LastfmSource));
+        manager.register("google-tts", Box::new(GoogleTtsSource));
+        manager.register("twitch", Box::new(TwitchSource));
+        manager.register("nicovideo", Box::new(NicovideoSource));
+        manager.register("reddit", Box::new(RedditSource));
+        manager.register("instagram", Box::new(InstagramSource));
+        manager.register("kwai", Box::new(KwaiSource));
+        manager.register("local", Box::new(LocalSource));
+        manager.register("http", Box::new(HttpSource));
+
+        manager.apply_config(config);
+        manager
+    }
+
+    /// Drops the sources disabled in `config`.
+    fn apply_config(&mut self, config: &Config) {
+        for name in config.sources.keys() {
+            if !self.known.contains(&name.as_str()) {
+                logger("warn", "Sources", &format!("Unknown source \"{}\" in config", name), None);
+            }
         }
+
+        let disabled: Vec<String> = self
+            .sources
+            .keys()
+            .filter(|name| !config.is_source_enabled(name))
+            .cloned()
+            .collect();
+        for name in disabled {
+            self.sources.remove(&name);
+            self.search_terms.retain(|_, source| *source != name);
+        }
+    }
+
+    fn register(&mut self, name: &'static str, source: Box<dyn Source>) {
+        self.known.push(name);
+        for term in source.search_terms() {
+            self.search_terms.insert(term, name.to_string());
+        }
+        self.sources.insert(name.to_string(), source);
+    }
+
+    pub fn source_names(&self) -> Vec<String> {
+        let mut names: Vec<String> = self.sources.keys().cloned().collect();
+        names.sort();
+        names
     }
 
     pub async fn load_folder(&mut self) {
         // In Rust, we manually register or use a registry.
     }
 
-    pub async fn search(&self, query: &str, nodelink: &NodelinkMock) -> Value {
-        // Simplified search logic
-        if let Some(source) = self.sources.get("youtube") {
-            return source.search(query, nodelink).await;
+    /// Entry point for `/v4/loadtracks`: URLs are resolved, anything else is searched
+    /// using its prefix (`scsearch:query`) or the default search source.
+    pub async fn load(&self, identifier: &str, nodelink: &NodelinkMock) -> Value {
+        let identifier = identifier.trim();
+        let result = if identifier.starts_with("http://") || identifier.starts_with("https://") {
+            self.resolve(identifier, nodelink).await
+        } else {
+            let (search_term, query) = match identifier.split_once(':') {
+                Some((term, query)) if self.search_terms.contains_key(term) => (term, query.trim()),
+                _ => (DEFAULT_SEARCH_TERM, identifier),
+            };
+            self.search(query, search_term, nodelink).await
+        };
+        normalize_load_result(result)
+    }
+
+    pub async fn search(&self, query: &str, search_term: &str, nodelink: &NodelinkMock) -> Value {
+        let source = self
+            .search_terms
+            .get(search_term)
+            .and_then(|name| self.sources.get(name));
+        if let Some(source) = source {
+            return source.search(query, search_term, nodelink).await;
         }
         serde_json::json!({
             "loadType": "empty",
@@ -36,6 +118,16 @@ impl SourceManager {
         })
     }
     
+    /// Asks the source that loaded the track (`info.sourceName`) where to stream it from.
+    pub async fn stream(&self, info: &Value, nodelink: &NodelinkMock) -> Result<StreamInput, String> {
+        let source_name = info["sourceName"].as_str().unwrap_or("http");
+        let source = self
+            .sources
+            .get(source_name)
+            .ok_or_else(|| format!("Source \"{}\" is not available", source_name))?;
+        source.get_stream(info, nodelink).await
+    }
+
     pub async fn resolve(&self, url: &str, nodelink: &NodelinkMock) -> Value {
         for source in self.sources.values() {
             let res = source.resolve(url, nodelink).await;
@@ -48,4 +140,32 @@ impl SourceManager {
             "data": {}
         })
     }
-}
\ No newline at end of file
+}
+
+/// Coerces a source result into one of the Lavalink `loadType` shapes.
+fn normalize_load_result(result: Value) -> Value {
+    match result["loadType"].as_str() {
+        Some("track") | Some("playlist") | Some("search") => result,
+        Some("empty") => json!({ "loadType": "empty", "data": {} }),
+        Some("error") => {
+            let data = &result["data"];
+            let message = data["message"].as_str().unwrap_or("Unknown error");
+            json!({
+                "loadType": "error",
+                "data": {
+                    "message": message,
+                    "severity": data["severity"].as_str().unwrap_or("fault"),
+                    "cause": data["cause"].as_str().unwrap_or(message)
+                }
+            })
+        }
+        _ => json!({
+            "loadType": "error",
+            "data": {
+                "message": "The source returned an invalid load result.",
+                "severity": "fault",
+                "cause": "Invalid loadType"
+            }
+        }),
+    }
+}
@@ -0,0 +1,84 @@
+use serde_json::json;
+use std::sync::{Arc, RwLock};
+use std::time::Duration;
+
+use crate::types::config::Config;
+use crate::types::stats::RustlinkMock;
+use crate::utils::{get_stats, update_statistics};
+
+/// Periodically pushes `playerUpdate` and `stats` ops to every connected session.
+/// Intervals are read from the current config before each wait, so reloads apply
+/// from the next update on.
+pub struct UpdateManager {
+    rustlink: Arc<RwLock<RustlinkMock>>,
+}
+
+impl UpdateManager {
+    pub fn new(rustlink: Arc<RwLock<RustlinkMock>>) -> Self {
+        Self { rustlink }
+    }
+
+    pub fn start(&self) {
+        let rustlink = self.rustlink.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval(&rustlink, |c| c.player_update_interval)).await;
+                send_player_updates(&rustlink).await;
+            }
+        });
+
+        let rustlink = self.rustlink.clone();
+        tokio::spawn(async move {
+            loop {
+                tokio::time::sleep(interval(&rustlink, |c| c.stats_interval)).await;
+                update_statistics(&rustlink).await;
+                send_stats(&rustlink);
+            }
+        });
+    }
+}
+
+fn interval(rustlink: &Arc<RwLock<RustlinkMock>>, seconds: fn(&Config) -> u64) -> Duration {
+    Duration::from_secs(seconds(&rustlink.read().unwrap().config))
+}
+
+async fn send_player_updates(rustlink: &Arc<RwLock<RustlinkMock>>) {
+    let sessions: Vec<_> = {
+        let rustlink_read = rustlink.read().unwrap();
+        rustlink_read
+            .sessions
+            .sessions
+            .values()
+            .filter(|s| s.is_connected())
+            .map(|s| (s.id.clone(), s.player_manager.clone()))
+            .collect()
+    };
+
+    for (session_id, player_manager) in sessions {
+        let players = player_manager.get_players().await;
+        let mut rustlink_write = rustlink.write().unwrap();
+        let Some(session) = rustlink_write.sessions.get_mut(&session_id) else {
+            continue;
+        };
+        for player in players {
+            session.send(json!({
+                "op": "playerUpdate",
+                "guildId": player["guildId"],
+                "state": player["state"]
+            }));
+        }
+    }
+}
+
+fn send_stats(rustlink: &Arc<RwLock<RustlinkMock>>) {
+    let stats = get_stats(&rustlink.read().unwrap());
+    let mut payload = serde_json::to_value(&stats).unwrap_or_default();
+    payload["op"] = json!("stats");
+
+    let mut rustlink_write = rustlink.write().unwrap();
+    for session in rustlink_write.sessions.sessions.values_mut() {
+        if session.is_connected() {
+            session.send(payload.clone());
+        }
+    }
+}
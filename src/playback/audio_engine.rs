@@ -1,4 +1,5 @@
 use std::collections::HashMap;
+use std::sync::{Arc, Mutex, MutexGuard, PoisonError};
 use tokio::sync::mpsc;
 
 use crate::managers::connection_manager::ConnectionManager;
@@ -6,88 +7,151 @@ use crate::managers::lyrics_manager::LyricsManager;
 use crate::managers::route_planner_manager::RoutePlannerManager;
 use crate::managers::source_manager::SourceManager;
 use crate::managers::stats_manager::StatsManager;
+use crate::playback::clock::{Clock, SystemClock};
+use crate::playback::pacer::FramePacer;
 use crate::playback::player::Player;
-use crate::types::audio_engine::AudioEngineCommand;
+use crate::types::audio_engine::{AudioEngineCommand, PlayerEvent};
+use crate::types::config::{AudioConfig, Config};
 use crate::types::http::NodelinkMock;
+use crate::types::stats::{FrameStats, RustlinkStats};
+
+type Players = HashMap<(String, String), Player>;
 
 pub struct AudioEngine {
-    players: HashMap<String, Player>,
+    // Shared with the pacing task, which ticks them. Commands only hold the lock for as
+    // long as they take synchronously, never across an await.
+    players: Arc<Mutex<Players>>,
     stats_manager: StatsManager,
-    source_manager: SourceManager,
-    lyrics_manager: LyricsManager,
+    source_manager: Arc<SourceManager>,
+    lyrics_manager: Arc<LyricsManager>,
     route_planner: RoutePlannerManager,
     connection_manager: ConnectionManager,
     receiver: mpsc::Receiver<AudioEngineCommand>,
-    nodelink: NodelinkMock,
+    events: mpsc::UnboundedSender<PlayerEvent>,
+    nodelink: Arc<NodelinkMock>,
+    audio_config: AudioConfig,
+    clock: Arc<dyn Clock>,
 }
 
 impl AudioEngine {
-    pub fn new(receiver: mpsc::Receiver<AudioEngineCommand>) -> Self {
+    pub fn new(
+        receiver: mpsc::Receiver<AudioEngineCommand>,
+        events: mpsc::UnboundedSender<PlayerEvent>,
+        config: &Config,
+    ) -> Self {
+        Self::with_clock(receiver, events, config, Arc::new(SystemClock::new()))
+    }
+
+    /// Paces frames with `clock` instead of wall time, e.g. a `VirtualClock`.
+    pub fn with_clock(
+        receiver: mpsc::Receiver<AudioEngineCommand>,
+        events: mpsc::UnboundedSender<PlayerEvent>,
+        config: &Config,
+        clock: Arc<dyn Clock>,
+    ) -> Self {
         Self {
-            players: HashMap::new(),
+            players: Arc::new(Mutex::new(HashMap::new())),
             stats_manager: StatsManager::new(),
-            source_manager: SourceManager::new(),
-            lyrics_manager: LyricsManager::new(),
-            route_planner: RoutePlannerManager::new(),
-            connection_manager: ConnectionManager::new(),
+            source_manager: Arc::new(SourceManager::new(config)),
+            lyrics_manager: Arc::new(LyricsManager::new()),
+            route_planner: RoutePlannerManager::new(config),
+            connection_manager: ConnectionManager::new(config),
             receiver,
-            nodelink: NodelinkMock {
+            events,
+            nodelink: Arc::new(NodelinkMock {
                 route_planner: None,
-            },
+            }),
+            audio_config: config.audio.clone(),
+            clock,
         }
     }
 
+    fn players(&self) -> MutexGuard<'_, Players> {
+        self.players.lock().unwrap_or_else(PoisonError::into_inner)
+    }
+
     pub async fn run(&mut self) {
         // Initialize managers
-        self.source_manager.load_folder().await;
-        self.lyrics_manager.load_folder().await;
+        if let Some(source_manager) = Arc::get_mut(&mut self.source_manager) {
+            source_manager.load_folder().await;
+        }
+        if let Some(lyrics_manager) = Arc::get_mut(&mut self.lyrics_manager) {
+            lyrics_manager.load_folder().await;
+        }
         self.connection_manager.start().await;
 
         println!("AudioEngine (Worker) started.");
 
+        // Frames are paced on their own task, so no command handler can hold up a tick
+        let pacing = tokio::spawn(pace(self.players.clone(), FramePacer::new(self.clock.clone())));
+
         while let Some(command) = self.receiver.recv().await {
             match command {
                 AudioEngineCommand::CreatePlayer {
-                    session_id: _,
+                    session_id,
                     guild_id,
-                    user_id: _,
+                    user_id,
                     voice,
                     resp,
                 } => {
-                    let result = if self.players.contains_key(&guild_id) {
+                    let key = (session_id.clone(), guild_id.clone());
+                    let result = if self.players().contains_key(&key) {
                         serde_json::json!({ "created": false, "reason": "Player already exists" })
                     } else {
-                        let mut player = Player::new(guild_id.clone());
+                        let mut player = Player::new(
+                            session_id,
+                            guild_id.clone(),
+                            user_id,
+                            self.events.clone(),
+                            self.audio_config.clone(),
+                        );
                         if let Some(v) = voice {
-                            // Mock voice update
-                            let _ = player.update_voice(
+                            player.update_voice(
                                 v.get("sessionId").and_then(|s| s.as_str()).unwrap_or(""),
                                 v.get("token").and_then(|s| s.as_str()).unwrap_or(""),
                                 v.get("endpoint").and_then(|s| s.as_str()).unwrap_or(""),
                             );
                         }
-                        self.players.insert(guild_id, player);
+                        self.players().insert(key, player);
                         serde_json::json!({ "created": true })
                     };
                     let _ = resp.send(result).await;
                 }
-                AudioEngineCommand::DestroyPlayer { guild_id, resp } => {
-                    let result = if let Some(player) = self.players.get_mut(&guild_id) {
+                AudioEngineCommand::DestroyPlayer {
+                    session_id,
+                    guild_id,
+                    resp,
+                } => {
+                    let removed = self.players().remove(&(session_id, guild_id));
+                    let result = if let Some(mut player) = removed {
                         player.destroy();
-                        self.players.remove(&guild_id);
                         serde_json::json!({ "destroyed": true })
                     } else {
                         serde_json::json!({ "destroyed": false, "reason": "Player not found" })
                     };
                     let _ = resp.send(result).await;
                 }
+                AudioEngineCommand::DestroySessionPlayers { session_id, resp } => {
+                    let removed: Vec<Player> = {
+                        let mut players = self.players();
+                        let keys: Vec<(String, String)> =
+                            players.keys().filter(|(sid, _)| *sid == session_id).cloned().collect();
+                        keys.iter().filter_map(|key| players.remove(key)).collect()
+                    };
+                    let destroyed = removed.len();
+                    for mut player in removed {
+                        player.destroy();
+                    }
+                    let _ = resp.send(serde_json::json!({ "destroyed": destroyed })).await;
+                }
                 AudioEngineCommand::PlayerCommand {
+                    session_id,
                     guild_id,
                     command,
                     args,
                     resp,
                 } => {
-                    let result = if let Some(player) = self.players.get_mut(&guild_id) {
+                    let result = if let Some(player) = self.players().get_mut(&(session_id, guild_id)) {
                         match command.as_str() {
                             "play" => {
                                 // args[0] = { encoded, info, ... }
@@ -101,11 +165,21 @@ impl AudioEngine {
                                         .get("info")
                                         .cloned()
                                         .unwrap_or(serde_json::Value::Null);
+                                    let user_data = track_payload
+                                        .get("userData")
+                                        .cloned()
+                                        .unwrap_or(serde_json::json!({}));
                                     let no_replace = track_payload
                                         .get("noReplace")
                                         .and_then(|b| b.as_bool())
                                         .unwrap_or(false);
-                                    serde_json::json!(player.play(encoded, info, no_replace))
+                                    let source_manager = self.source_manager.clone();
+                                    let nodelink = self.nodelink.clone();
+                                    let stream_info = info.clone();
+                                    let stream = async move {
+                                        source_manager.stream(&stream_info, &nodelink).await
+                                    };
+                                    serde_json::json!(player.play(encoded, info, user_data, no_replace, stream))
                                 } else {
                                     serde_json::json!(false)
                                 }
@@ -129,6 +203,23 @@ impl AudioEngine {
                                 let filters = args.get(0).cloned().unwrap_or(serde_json::json!({}));
                                 serde_json::json!(player.set_filters(filters))
                             }
+                            "endTime" => {
+                                let end_time = args.first().and_then(|v| v.as_i64());
+                                serde_json::json!(player.set_end_time(end_time))
+                            }
+                            "userData" => {
+                                let user_data = args.first().cloned().unwrap_or(serde_json::json!({}));
+                                serde_json::json!(player.set_user_data(user_data))
+                            }
+                            "updateVoice" => {
+                                let voice = args.first().cloned().unwrap_or(serde_json::json!({}));
+                                player.update_voice(
+                                    voice.get("sessionId").and_then(|s| s.as_str()).unwrap_or(""),
+                                    voice.get("token").and_then(|s| s.as_str()).unwrap_or(""),
+                                    voice.get("endpoint").and_then(|s| s.as_str()).unwrap_or(""),
+                                );
+                                serde_json::json!(true)
+                            }
                             // ... other commands
                             _ => serde_json::json!({ "error": "Unknown command" }),
                         }
@@ -138,43 +229,109 @@ impl AudioEngine {
                     let _ = resp.send(result).await;
                 }
                 AudioEngineCommand::LoadTracks { identifier, resp } => {
-                    let result = if identifier.starts_with("http") {
-                        self.source_manager
-                            .resolve(&identifier, &self.nodelink)
-                            .await
-                    } else {
-                        self.source_manager
-                            .search(&identifier, &self.nodelink)
-                            .await
-                    };
-                    let _ = resp.send(result).await;
+                    // Loading hits the network, so it must not stall the command loop.
+                    let source_manager = self.source_manager.clone();
+                    let nodelink = self.nodelink.clone();
+                    tokio::spawn(async move {
+                        let result = source_manager.load(&identifier, &nodelink).await;
+                        let _ = resp.send(result).await;
+                    });
                 }
                 AudioEngineCommand::LoadLyrics {
                     decoded_track,
                     resp,
                 } => {
-                    let result = self.lyrics_manager.load_lyrics(&decoded_track).await;
-                    let _ = resp.send(result).await;
+                    let lyrics_manager = self.lyrics_manager.clone();
+                    tokio::spawn(async move {
+                        let result = lyrics_manager.load_lyrics(&decoded_track).await;
+                        let _ = resp.send(result).await;
+                    });
                 }
                 AudioEngineCommand::GetSources { resp } => {
-                    let _ = resp
-                        .send(vec!["youtube".to_string(), "soundcloud".to_string()])
-                        .await;
+                    let _ = resp.send(self.source_manager.source_names()).await;
                 }
-                AudioEngineCommand::GetPlayer { guild_id, resp } => {
-                    let result = if let Some(player) = self.players.get(&guild_id) {
-                        player.to_json()
-                    } else {
-                        serde_json::Value::Null
-                    };
+                AudioEngineCommand::GetPlayer {
+                    session_id,
+                    guild_id,
+                    resp,
+                } => {
+                    let player = self.players().get(&(session_id, guild_id)).map(Player::to_json);
+                    let result = player.unwrap_or(serde_json::Value::Null);
                     let _ = resp.send(result).await;
                 }
-                AudioEngineCommand::GetPlayers { resp } => {
-                    let players: Vec<serde_json::Value> =
-                        self.players.values().map(|p| p.to_json()).collect();
+                AudioEngineCommand::GetPlayers { session_id, resp } => {
+                    let players: Vec<serde_json::Value> = self
+                        .players()
+                        .iter()
+                        .filter(|((sid, _), _)| *sid == session_id)
+                        .map(|(_, p)| p.to_json())
+                        .collect();
                     let _ = resp.send(players).await;
                 }
+                AudioEngineCommand::GetStats { resp } => {
+                    let stats = self.stats();
+                    let _ = resp.send(stats).await;
+                }
+                AudioEngineCommand::ReloadConfig { config, resp } => {
+                    // Loads already running keep the previous SourceManager until they finish
+                    self.source_manager = Arc::new(SourceManager::new(&config));
+                    self.route_planner.reload(&config);
+                    self.connection_manager.reload(&config);
+                    self.audio_config = config.audio.clone();
+                    for player in self.players.lock().unwrap_or_else(PoisonError::into_inner).values_mut() {
+                        player.set_audio_config(&self.audio_config);
+                    }
+                    let _ = resp.send(serde_json::json!({ "reloaded": true })).await;
+                }
+            }
+        }
+        pacing.abort();
+    }
+
+    /// Player counts plus frame stats averaged over every player with a full minute of data.
+    fn stats(&self) -> RustlinkStats {
+        let players = self.players();
+        let mut playing_players = 0;
+        let mut counted = Vec::new();
+        for player in players.values() {
+            if !player.is_playing() {
+                continue;
+            }
+            playing_players += 1;
+            if let Some(frame_stats) = player.frame_stats() {
+                counted.push(frame_stats);
+            }
+        }
+
+        let frame_stats = (!counted.is_empty()).then(|| {
+            let count = counted.len() as u64;
+            let average = |field: fn(&FrameStats) -> u64| counted.iter().map(field).sum::<u64>() / count;
+            FrameStats {
+                sent: average(|f| f.sent),
+                nulled: average(|f| f.nulled),
+                deficit: average(|f| f.deficit),
+                expected: average(|f| f.expected),
+            }
+        });
+
+        RustlinkStats {
+            players: players.len(),
+            playing_players,
+            frame_stats,
+        }
+    }
+}
+
+/// Ticks every player on the pacer's grid until aborted.
+async fn pace(players: Arc<Mutex<Players>>, mut pacer: FramePacer) {
+    loop {
+        let tick = pacer.next().await;
+        let mut players = players.lock().unwrap_or_else(PoisonError::into_inner);
+        for player in players.values_mut() {
+            for _ in 0..tick.due {
+                player.tick();
             }
+            player.frames_missed(tick.dropped);
         }
     }
 }
@@ -0,0 +1,66 @@
+use std::future::Future;
+use std::pin::Pin;
+#[cfg(test)]
+use std::sync::Mutex;
+use std::time::Duration;
+use tokio::time::Instant;
+
+/// Time source for frame pacing. Times are offsets from when the clock was created.
+pub trait Clock: Send + Sync {
+    fn now(&self) -> Duration;
+    fn sleep_until(&self, deadline: Duration) -> Pin<Box<dyn Future<Output = ()> + Send + '_>>;
+}
+
+/// Wall-clock time through the tokio timer.
+pub struct SystemClock {
+    start: Instant,
+}
+
+impl SystemClock {
+    pub fn new() -> Self {
+        Self { start: Instant::now() }
+    }
+}
+
+impl Clock for SystemClock {
+    fn now(&self) -> Duration {
+        self.start.elapsed()
+    }
+
+    fn sleep_until(&self, deadline: Duration) -> Pin<Box<dyn Future<Output = ()> + Send + '_>> {
+        Box::pin(tokio::time::sleep_until(self.start + deadline))
+    }
+}
+
+/// Clock that only moves when told to, so pacing can be driven deterministically.
+/// Sleeping jumps straight to the deadline; `advance` simulates time lost elsewhere.
+#[cfg(test)]
+pub struct VirtualClock {
+    now: Mutex<Duration>,
+}
+
+#[cfg(test)]
+impl VirtualClock {
+    pub fn new() -> Self {
+        Self { now: Mutex::new(Duration::ZERO) }
+    }
+
+    pub fn advance(&self, by: Duration) {
+        *self.now.lock().unwrap() += by;
+    }
+}
+
+#[cfg(test)]
+impl Clock for VirtualClock {
+    fn now(&self) -> Duration {
+        *self.now.lock().unwrap()
+    }
+
+    fn sleep_until(&self, deadline: Duration) -> Pin<Box<dyn Future<Output = ()> + Send + '_>> {
+        let mut now = self.now.lock().unwrap();
+        if *now < deadline {
+            *now = deadline;
+        }
+        Box::pin(std::future::ready(()))
+    }
+}
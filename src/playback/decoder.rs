@@ -0,0 +1,700 @@
+use std::fs::File;
+use std::io::{Seek, SeekFrom};
+use std::path::{Path, PathBuf};
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{Receiver, SyncSender};
+use symphonia::core::audio::SampleBuffer;
+use symphonia::core::codecs::{
+    CodecParameters, CODEC_TYPE_MP3, CODEC_TYPE_NULL, CODEC_TYPE_OPUS, Decoder as SymphoniaDecoder, DecoderOptions,
+};
+use symphonia::core::errors::{Error as SymphoniaError, SeekErrorKind};
+use symphonia::core::formats::{FormatOptions, FormatReader, Packet, SeekMode, SeekTo};
+use symphonia::core::io::{MediaSource, MediaSourceStream};
+use symphonia::core::meta::MetadataOptions;
+use symphonia::core::probe::Hint;
+use symphonia::core::units::Time;
+use tokio::runtime::Handle;
+
+use crate::types::config::ResamplingQuality;
+use super::hls_stream::HlsPlaylist;
+use super::http_stream::HttpStream;
+use super::mp3_index::{self, Mp3Index};
+use super::opus::{packet_samples, OpusDecoder, CHANNELS, FRAME_SIZE};
+use super::resampler::Resampler;
+use super::SAMPLE_RATE;
+
+// Audio decoded before a seek target so Opus and MP3 decoders have settled by then
+const SEEK_PREROLL_SECONDS: f64 = 0.08;
+
+pub enum StreamChunk {
+    /// 48kHz stereo interleaved PCM.
+    Pcm(Vec<i16>),
+    /// A 20ms Opus packet passed through undecoded.
+    Opus(Vec<u8>),
+    /// The seek with this id is done; everything after it is from the new position. On an
+    /// error the stream goes on from where it was.
+    Seeked(u64, Result<(), String>),
+    /// The track ran out. The decoder stays around for seeks back into it.
+    End,
+}
+
+/// The next piece of a track, or the error that stopped decoding.
+pub type DecodedChunk = Result<StreamChunk, String>;
+
+/// A seek request for the decoder thread: its id and the target in milliseconds.
+pub type SeekRequest = (u64, u64);
+
+/// Where a track's audio is read from.
+#[derive(Clone)]
+pub enum StreamInput {
+    File(PathBuf),
+    Http(String),
+    /// URL of an HLS playlist.
+    Hls(String),
+}
+
+enum Decoder {
+    Opus(OpusDecoder),
+    Symphonia(Box<dyn SymphoniaDecoder>),
+}
+
+/// Demuxes and decodes `input` until it ends or `output` is dropped. Runs on a
+/// blocking thread; `handle` drives the HTTP requests of remote streams.
+///
+/// While `passthrough` is set, packets of 48kHz stereo Opus sources are sent as they
+/// are, skipping the decode and the player's re-encode.
+pub fn decode(
+    input: StreamInput,
+    handle: Handle,
+    resampling_quality: ResamplingQuality,
+    passthrough: Arc<AtomicBool>,
+    seeks: Receiver<SeekRequest>,
+    output: SyncSender<DecodedChunk>,
+) {
+    if let Err(e) = run(input, handle, resampling_quality, &passthrough, &seeks, &output) {
+        let _ = output.send(Err(e));
+    }
+}
+
+fn run(
+    input: StreamInput,
+    handle: Handle,
+    resampling_quality: ResamplingQuality,
+    passthrough: &AtomicBool,
+    seeks: &Receiver<SeekRequest>,
+    output: &SyncSender<DecodedChunk>,
+) -> Result<(), String> {
+    let mut stream = Stream::open(input, handle, resampling_quality)?;
+    let can_pass_through = stream.params.codec == CODEC_TYPE_OPUS
+        && stream.params.sample_rate == Some(SAMPLE_RATE as u32)
+        && opus_channels(&stream.params) == Some(CHANNELS);
+    let mut pending_seek = None;
+
+    loop {
+        // Only the latest of several queued seeks matters
+        let mut seek = pending_seek.take();
+        while let Ok(request) = seeks.try_recv() {
+            seek = Some(request);
+        }
+        let mut past_end = false;
+        if let Some((id, position)) = seek {
+            let result = match stream.seek(position) {
+                Ok(in_range) => {
+                    past_end = !in_range;
+                    Ok(())
+                }
+                Err(e) => Err(format!("Failed to seek to {}ms: {}", position, e)),
+            };
+            if output.send(Ok(StreamChunk::Seeked(id, result))).is_err() {
+                return Ok(());
+            }
+        }
+
+        let packet = match stream.format()?.next_packet() {
+            Ok(packet) if !past_end => Some(packet),
+            Ok(_) => None,
+            Err(SymphoniaError::IoError(e)) if e.kind() == std::io::ErrorKind::UnexpectedEof => None,
+            Err(e) => return Err(format!("Failed to read stream: {}", e)),
+        };
+        let Some(packet) = packet else {
+            if let Some(pcm) = stream.flush() {
+                let _ = output.send(Ok(StreamChunk::Pcm(pcm)));
+            }
+            if output.send(Ok(StreamChunk::End)).is_err() {
+                return Ok(());
+            }
+            // Wait for a seek back into the track until the player drops it
+            match seeks.recv() {
+                Ok(request) => {
+                    pending_seek = Some(request);
+                    continue;
+                }
+                Err(_) => return Ok(()),
+            }
+        };
+        if packet.track_id() != stream.track_id {
+            continue;
+        }
+
+        // Discord needs 20ms per packet, anything else goes through the encoder
+        if can_pass_through
+            && passthrough.load(Ordering::Relaxed)
+            && packet_samples(&packet.data) == Some(FRAME_SIZE)
+        {
+            // Skipping towards a seek target goes by whole packets here, and the
+            // pre-skip is left to the client as with any Opus stream
+            if stream.skip >= FRAME_SIZE * CHANNELS {
+                stream.skip -= FRAME_SIZE * CHANNELS;
+                continue;
+            }
+            stream.skip = 0;
+            if output.send(Ok(StreamChunk::Opus(packet.data.to_vec()))).is_err() {
+                return Ok(());
+            }
+            continue;
+        }
+
+        let Some(pcm) = stream.decode(&packet)? else {
+            continue;
+        };
+        if output.send(Ok(StreamChunk::Pcm(pcm))).is_err() {
+            // The player dropped the resource
+            return Ok(());
+        }
+    }
+}
+
+/// An opened track and the decoding state that has to be reset on seeks.
+struct Stream {
+    // Kept to probe the source again, which MP3 and HLS seeks do
+    hint: Hint,
+    // Only missing if an MP3 seek lost the source
+    format: Option<Box<dyn FormatReader>>,
+    hls: Option<HlsPlaylist>,
+    // Sources without range support can only be read on, which an MP3 seek can't do
+    seekable: bool,
+    track_id: u32,
+    params: CodecParameters,
+    decoder: Decoder,
+    resampling_quality: ResamplingQuality,
+    resampler: Option<(u32, Resampler)>,
+    samples: Option<SampleBuffer<i16>>,
+    // Output samples still to drop: the Opus pre-skip, or the way to a seek target
+    skip: usize,
+    // Read on the first seek of an MP3 stream, `Some(None)` if it has no index
+    mp3_index: Option<Option<Mp3Index>>,
+}
+
+impl Stream {
+    fn open(input: StreamInput, handle: Handle, resampling_quality: ResamplingQuality) -> Result<Self, String> {
+        let mut hls = None;
+        let (source, hint): (Box<dyn MediaSource>, _) = match input {
+            StreamInput::File(path) => open_file(&path)?,
+            StreamInput::Http(url) => open_http(&url, handle)?,
+            StreamInput::Hls(url) => {
+                let playlist = hls.insert(HlsPlaylist::load(&url, handle)?);
+                (Box::new(playlist.stream_from(0)), playlist.hint())
+            }
+        };
+        let seekable = source.is_seekable();
+        let format = probe(MediaSourceStream::new(source, Default::default()), &hint)?;
+
+        let track = format
+            .tracks()
+            .iter()
+            .find(|t| t.codec_params.codec != CODEC_TYPE_NULL)
+            .ok_or("No audio track found")?;
+        let track_id = track.id;
+        let params = track.codec_params.clone();
+
+        let decoder = if params.codec == CODEC_TYPE_OPUS {
+            Decoder::Opus(OpusDecoder::new()?)
+        } else {
+            let decoder = symphonia::default::get_codecs()
+                .make(&params, &DecoderOptions::default())
+                .map_err(|e| format!("Unsupported codec: {}", e))?;
+            Decoder::Symphonia(decoder)
+        };
+
+        Ok(Self {
+            hint,
+            format: Some(format),
+            hls,
+            seekable,
+            track_id,
+            skip: opus_pre_skip(&params) * CHANNELS,
+            params,
+            decoder,
+            resampling_quality,
+            resampler: None,
+            samples: None,
+            mp3_index: None,
+        })
+    }
+
+    /// Decodes a packet to 48kHz stereo, `None` if nothing is left after skipping.
+    fn decode(&mut self, packet: &Packet) -> Result<Option<Vec<i16>>, String> {
+        let pcm = match &mut self.decoder {
+            Decoder::Opus(decoder) => decoder.decode(&packet.data)?.to_vec(),
+            Decoder::Symphonia(decoder) => {
+                let decoded = match decoder.decode(packet) {
+                    Ok(decoded) => decoded,
+                    // A corrupt packet shouldn't end the track
+                    Err(SymphoniaError::DecodeError(_)) => return Ok(None),
+                    Err(e) => return Err(format!("Failed to decode stream: {}", e)),
+                };
+                let spec = *decoded.spec();
+                let buffer = match &mut self.samples {
+                    Some(buffer) if buffer.capacity() >= decoded.capacity() => buffer,
+                    _ => self.samples.insert(SampleBuffer::new(decoded.capacity() as u64, spec)),
+                };
+                buffer.copy_interleaved_ref(decoded);
+                let stereo = to_stereo(buffer.samples(), spec.channels.count());
+
+                if spec.rate == SAMPLE_RATE as u32 {
+                    stereo
+                } else {
+                    let resampler = match &mut self.resampler {
+                        Some((rate, resampler)) if *rate == spec.rate => resampler,
+                        _ => {
+                            let resampler = Resampler::new(spec.rate, SAMPLE_RATE as u32, self.resampling_quality)?;
+                            &mut self.resampler.insert((spec.rate, resampler)).1
+                        }
+                    };
+                    resampler.process(&stereo)
+                }
+            }
+        };
+
+        Ok(self.drop_skipped(pcm))
+    }
+
+    /// What the resampler still holds at the end of the stream.
+    fn flush(&mut self) -> Option<Vec<i16>> {
+        let pcm = self.resampler.as_mut()?.1.flush();
+        self.drop_skipped(pcm)
+    }
+
+    fn drop_skipped(&mut self, mut pcm: Vec<i16>) -> Option<Vec<i16>> {
+        if self.skip > 0 {
+            let skipped = self.skip.min(pcm.len());
+            pcm.drain(..skipped);
+            self.skip -= skipped;
+        }
+        (!pcm.is_empty()).then_some(pcm)
+    }
+
+    /// Moves to `position_ms` through the container's index, then sets up skipping the
+    /// decoded audio up to the exact sample. `false` if the position is past the end.
+    fn seek(&mut self, position_ms: u64) -> Result<bool, String> {
+        let target = position_ms as f64 / 1000.0;
+        let from = (target - SEEK_PREROLL_SECONDS).max(0.0);
+
+        let start = if self.hls.is_some() {
+            self.seek_hls(from)?
+        } else if let Some(start) = self.seek_mp3(from)? {
+            Some(start)
+        } else {
+            self.seek_container(from)?
+        };
+        let Some(start) = start else {
+            return Ok(false);
+        };
+
+        match &mut self.decoder {
+            Decoder::Opus(decoder) => *decoder = OpusDecoder::new()?,
+            Decoder::Symphonia(decoder) => decoder.reset(),
+        }
+        self.resampler = None;
+        // Opus timestamps count the pre-skip, which comes before the track's time zero
+        let skip = ((target - start) * SAMPLE_RATE as f64).round() + opus_pre_skip(&self.params) as f64;
+        self.skip = skip.max(0.0) as usize * CHANNELS;
+        Ok(true)
+    }
+
+    /// Seeks through the demuxer. Returns the time it resumes at, `None` past the end.
+    fn seek_container(&mut self, seconds: f64) -> Result<Option<f64>, String> {
+        // MP3 without an index can only be located by its average bitrate,
+        // accurate mode would read it from the start
+        let mode = if self.params.codec == CODEC_TYPE_MP3 { SeekMode::Coarse } else { SeekMode::Accurate };
+        let track_id = self.track_id;
+        let to = || SeekTo::Time { time: Time::from(seconds), track_id: Some(track_id) };
+        let seeked = match self.format()?.seek(mode, to()) {
+            // Without a frame count the average bitrate is unknown as well
+            Err(SymphoniaError::SeekError(SeekErrorKind::Unseekable)) if mode == SeekMode::Coarse => {
+                self.format()?.seek(SeekMode::Accurate, to())
+            }
+            seeked => seeked,
+        };
+        let seeked = match seeked {
+            Ok(seeked) => seeked,
+            Err(SymphoniaError::SeekError(SeekErrorKind::OutOfRange)) => return Ok(None),
+            Err(e) => return Err(e.to_string()),
+        };
+        let time_base = self.params.time_base.ok_or("Track has no time base")?;
+        let time = time_base.calc_time(seeked.actual_ts);
+        Ok(Some(time.seconds as f64 + time.frac))
+    }
+
+    /// Plays an HLS stream again from the segment holding `seconds`, as segments carry no
+    /// index across the stream. Returns the time it resumes at, `None` past the end.
+    fn seek_hls(&mut self, seconds: f64) -> Result<Option<f64>, String> {
+        let Some(playlist) = &self.hls else {
+            return Ok(None);
+        };
+        let Some(segment) = playlist.segment_at(seconds) else {
+            return Ok(None);
+        };
+        let start = playlist.start_of(segment);
+        self.reprobe(MediaSourceStream::new(Box::new(playlist.stream_from(segment)), Default::default()))?;
+        Ok(Some(start))
+    }
+
+    fn format(&mut self) -> Result<&mut Box<dyn FormatReader>, String> {
+        self.format.as_mut().ok_or_else(|| "The stream was lost in a failed seek".to_string())
+    }
+
+    /// Seeks raw MP3 through its Xing or VBRI table by probing the source again from the
+    /// byte offset for `seconds`. Returns the time it resumes at, `None` without a table.
+    fn seek_mp3(&mut self, seconds: f64) -> Result<Option<f64>, String> {
+        if !self.seekable || self.params.codec != CODEC_TYPE_MP3 || matches!(self.mp3_index, Some(None)) {
+            return Ok(None);
+        }
+        let mut source = self.format.take().ok_or("The stream was lost in a failed seek")?.into_inner();
+        // The table is read on the first seek; without one the stream is probed again from
+        // the start and seeked by its bitrate instead
+        let start = match self.mp3_index.get_or_insert_with(|| Mp3Index::read(&mut source)) {
+            Some(index) => {
+                let frame = mp3_index::sync(&mut source, index.locate(seconds))
+                    .ok_or("No MP3 frame found at the seek position")?;
+                Some(index.time_at(frame))
+            }
+            None => {
+                source
+                    .seek(SeekFrom::Start(0))
+                    .map_err(|e| format!("Failed to seek stream: {}", e))?;
+                None
+            }
+        };
+        self.reprobe(source)?;
+        Ok(start)
+    }
+
+    /// Replaces the demuxer with one reading from `source`, which has to hold the same track.
+    fn reprobe(&mut self, source: MediaSourceStream) -> Result<(), String> {
+        let format = probe(source, &self.hint)?;
+        self.track_id = format
+            .tracks()
+            .iter()
+            .find(|t| t.codec_params.codec == self.params.codec)
+            .ok_or("No audio track found after seeking")?
+            .id;
+        self.format = Some(format);
+        Ok(())
+    }
+}
+
+fn probe(stream: MediaSourceStream, hint: &Hint) -> Result<Box<dyn FormatReader>, String> {
+    let format_options = FormatOptions {
+        enable_gapless: true,
+        ..Default::default()
+    };
+    let probed = symphonia::default::get_probe()
+        .format(hint, stream, &format_options, &MetadataOptions::default())
+        .map_err(|e| format!("Unsupported or unrecognized format: {}", e))?;
+    Ok(probed.format)
+}
+
+fn open_file(path: &Path) -> Result<(Box<dyn MediaSource>, Hint), String> {
+    let mut hint = Hint::new();
+    if let Some(extension) = path.extension().and_then(|e| e.to_str()) {
+        hint.with_extension(extension);
+    }
+    let file = File::open(path).map_err(|e| format!("Failed to open {}: {}", path.display(), e))?;
+    Ok((Box::new(file), hint))
+}
+
+fn open_http(url: &str, handle: Handle) -> Result<(Box<dyn MediaSource>, Hint), String> {
+    let mut hint = Hint::new();
+    let stream = HttpStream::open(url, handle)?;
+    if let Some(content_type) = &stream.content_type {
+        hint.mime_type(content_type.split(';').next().unwrap_or(content_type).trim());
+    }
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    if let Some(extension) = Path::new(path).extension().and_then(|e| e.to_str()) {
+        hint.with_extension(extension);
+    }
+    Ok((Box::new(stream), hint))
+}
+
+/// The Opus identification header, which Matroska keeps as the codec private data.
+fn opus_head(params: &CodecParameters) -> Option<&[u8]> {
+    let head = params.extra_data.as_deref()?;
+    head.starts_with(b"OpusHead").then_some(head)
+}
+
+/// Channel count of an Opus track; Matroska leaves it out of the codec parameters.
+fn opus_channels(params: &CodecParameters) -> Option<usize> {
+    params
+        .channels
+        .map(|c| c.count())
+        .or_else(|| opus_head(params)?.get(9).map(|&channels| channels as usize))
+}
+
+/// Frames of encoder delay at the start of an Opus stream, 0 for other codecs.
+fn opus_pre_skip(params: &CodecParameters) -> usize {
+    if params.codec != CODEC_TYPE_OPUS {
+        return 0;
+    }
+    params
+        .delay
+        .map(|delay| delay as usize)
+        .or_else(|| Some(u16::from_le_bytes(opus_head(params)?.get(10..12)?.try_into().ok()?) as usize))
+        .unwrap_or(0)
+}
+
+/// Maps interleaved PCM with any channel count to stereo.
+fn to_stereo(samples: &[i16], channels: usize) -> Vec<i16> {
+    match channels {
+        2 => samples.to_vec(),
+        1 => samples.iter().flat_map(|&s| [s, s]).collect(),
+        _ => samples.chunks_exact(channels).flat_map(|frame| [frame[0], frame[1]]).collect(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::mpsc;
+    use tokio::io::{AsyncReadExt, AsyncWriteExt};
+    use tokio::net::TcpListener;
+    use tokio::runtime::Runtime;
+
+    fn fixture(name: &str) -> PathBuf {
+        Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures").join(name)
+    }
+
+    /// Decodes a file from `tests/fixtures` to the PCM the player would get, from
+    /// `seek_ms` on if set.
+    fn decode_fixture(name: &str, seek_ms: Option<u64>) -> Vec<i16> {
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        decode_input(&runtime, StreamInput::File(fixture(name)), seek_ms)
+    }
+
+    fn decode_input(runtime: &Runtime, input: StreamInput, seek_ms: Option<u64>) -> Vec<i16> {
+        let name = match &input {
+            StreamInput::File(path) => path.display().to_string(),
+            StreamInput::Http(url) | StreamInput::Hls(url) => url.clone(),
+        };
+        let (sender, receiver) = mpsc::sync_channel(4);
+        let (seeks, seek_receiver) = mpsc::channel();
+        let handle = runtime.handle().clone();
+        let passthrough = Arc::new(AtomicBool::new(false));
+        if let Some(position) = seek_ms {
+            seeks.send((1, position)).unwrap();
+        }
+        let decoder = std::thread::spawn(move || {
+            decode(input, handle, ResamplingQuality::High, passthrough, seek_receiver, sender)
+        });
+
+        let mut pcm = Vec::new();
+        for chunk in receiver.iter() {
+            match chunk {
+                Ok(StreamChunk::Pcm(samples)) => pcm.extend(samples),
+                Ok(StreamChunk::Seeked(_, result)) => {
+                    result.unwrap_or_else(|e| panic!("{}: {}", name, e));
+                    pcm.clear();
+                }
+                Ok(StreamChunk::End) => break,
+                Ok(_) => {}
+                Err(e) => panic!("{}: {}", name, e),
+            }
+        }
+        drop(seeks);
+        decoder.join().unwrap();
+        pcm
+    }
+
+    /// Frequency of one channel, counted from its rising zero crossings.
+    fn frequency(pcm: &[i16], channel: usize) -> f64 {
+        let samples: Vec<i16> = pcm.iter().skip(channel).step_by(2).copied().collect();
+        let crossings: Vec<usize> = (1..samples.len())
+            .filter(|&i| samples[i - 1] < 0 && samples[i] >= 0)
+            .collect();
+        let (first, last) = (crossings[0], crossings[crossings.len() - 1]);
+        (crossings.len() - 1) as f64 * SAMPLE_RATE as f64 / (last - first) as f64
+    }
+
+    fn rms(pcm: &[i16]) -> f64 {
+        (pcm.iter().map(|&s| (s as f64).powi(2)).sum::<f64>() / pcm.len() as f64).sqrt()
+    }
+
+    #[test]
+    fn decodes_every_container_to_48khz_stereo() {
+        // File, its duration in seconds and the tone on each channel, if it has a clean one.
+        // The Opus files lose the 312 pre-skip samples of their 13 frames.
+        let fixtures = [
+            ("sine.webm", 0.2535, Some((440.0, 660.0))), // Opus, 48kHz stereo
+            ("sine.ogg", 0.2535, Some((440.0, 660.0))),  // Opus, 48kHz stereo
+            ("sine.m4a", 0.25, Some((440.0, 660.0))),    // ALAC, 22.05kHz stereo
+            ("sine.flac", 0.25, Some((440.0, 660.0))),   // 32kHz stereo
+            ("sine.wav", 0.25, Some((440.0, 440.0))),    // 44.1kHz mono
+            ("sine.mp3", 0.2612, None),                  // 10 frames, 44.1kHz mono
+            ("sine-xing.mp3", 0.2612, None),             // The same behind a Xing tag
+        ];
+
+        for (name, duration, tones) in fixtures {
+            let pcm = decode_fixture(name, None);
+            assert_eq!(pcm.len() % CHANNELS, 0, "{}", name);
+
+            let seconds = (pcm.len() / CHANNELS) as f64 / SAMPLE_RATE as f64;
+            assert!((seconds - duration).abs() < 0.005, "{}: decoded {:.3}s, expected {:.3}s", name, seconds, duration);
+            assert!(rms(&pcm) > 500.0, "{}: output is silent", name);
+
+            if let Some((left, right)) = tones {
+                for (channel, tone) in [left, right].into_iter().enumerate() {
+                    let measured = frequency(&pcm, channel);
+                    assert!(
+                        (measured / tone - 1.0).abs() < 0.01,
+                        "{}: channel {} is {:.1}Hz, expected {}Hz",
+                        name,
+                        channel,
+                        measured,
+                        tone
+                    );
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn seeks_to_the_exact_sample() {
+        let fixtures = [
+            ("sine.webm", 0.2535),
+            ("sine.ogg", 0.2535),
+            ("sine.m4a", 0.25),
+            ("sine.flac", 0.25),
+            ("sine.wav", 0.25),
+            ("sine.mp3", 0.2612),
+            ("sine-xing.mp3", 0.2612),
+        ];
+
+        for (name, duration) in fixtures {
+            let pcm = decode_fixture(name, Some(120));
+            let seconds = (pcm.len() / CHANNELS) as f64 / SAMPLE_RATE as f64;
+            let expected = duration - 0.12;
+            assert!((seconds - expected).abs() < 0.005, "{}: {:.3}s left, expected {:.3}s", name, seconds, expected);
+        }
+    }
+
+    /// Serves `files` over HTTP on a local port and returns its base URL.
+    fn serve(runtime: &Runtime, files: Vec<(String, Vec<u8>)>) -> String {
+        let listener = runtime.block_on(TcpListener::bind("127.0.0.1:0")).unwrap();
+        let address = listener.local_addr().unwrap();
+        runtime.spawn(async move {
+            let files = Arc::new(files);
+            loop {
+                let (mut socket, _) = listener.accept().await.unwrap();
+                let files = files.clone();
+                tokio::spawn(async move {
+                    let mut request = Vec::new();
+                    let mut buf = [0u8; 1024];
+                    while !request.ends_with(b"\r\n\r\n") {
+                        match socket.read(&mut buf).await {
+                            Ok(0) | Err(_) => return,
+                            Ok(read) => request.extend_from_slice(&buf[..read]),
+                        }
+                    }
+                    let request = String::from_utf8_lossy(&request);
+                    let path = request.split(' ').nth(1).unwrap_or("");
+                    let response = match files.iter().find(|(name, _)| path == format!("/{}", name)) {
+                        Some((_, body)) => {
+                            let mut response =
+                                format!("HTTP/1.1 200 OK\r\nContent-Length: {}\r\nConnection: close\r\n\r\n", body.len())
+                                    .into_bytes();
+                            response.extend_from_slice(body);
+                            response
+                        }
+                        None => b"HTTP/1.1 404 Not Found\r\nContent-Length: 0\r\nConnection: close\r\n\r\n".to_vec(),
+                    };
+                    let _ = socket.write_all(&response).await;
+                });
+            }
+        });
+        format!("http://{}", address)
+    }
+
+    #[test]
+    fn seeks_hls_by_segment() {
+        // sine.mp3 cut into segments of three 1152 sample frames, the last one holding one
+        const FRAME_BYTES: usize = 208;
+        const FRAME_SECONDS: f64 = 1152.0 / 44100.0;
+        let mp3 = std::fs::read(fixture("sine.mp3")).unwrap();
+        let mut files = Vec::new();
+        let mut playlist = "#EXTM3U\n#EXT-X-TARGETDURATION:1\n".to_string();
+        for (i, segment) in mp3.chunks(FRAME_BYTES * 3).enumerate() {
+            let frames = segment.len() / FRAME_BYTES;
+            playlist += &format!("#EXTINF:{:.6},\nsegments/{}.mp3\n", frames as f64 * FRAME_SECONDS, i);
+            files.push((format!("segments/{}.mp3", i), segment.to_vec()));
+        }
+        playlist += "#EXT-X-ENDLIST\n";
+        files.push(("index.m3u8".to_string(), playlist.into_bytes()));
+
+        let runtime = Runtime::new().unwrap();
+        let url = format!("{}/index.m3u8", serve(&runtime, files));
+        let seconds = |pcm: Vec<i16>| (pcm.len() / CHANNELS) as f64 / SAMPLE_RATE as f64;
+
+        let whole = seconds(decode_input(&runtime, StreamInput::Hls(url.clone()), None));
+        assert!((whole - 10.0 * FRAME_SECONDS).abs() < 0.005, "decoded {:.3}s", whole);
+
+        // The preroll reaches back into the second segment, which starts at frame 3
+        for position in [100, 200, 250] {
+            let left = seconds(decode_input(&runtime, StreamInput::Hls(url.clone()), Some(position)));
+            let expected = 10.0 * FRAME_SECONDS - position as f64 / 1000.0;
+            assert!((left - expected).abs() < 0.005, "{}ms: {:.3}s left, expected {:.3}s", position, left, expected);
+        }
+
+        let past_end = decode_input(&runtime, StreamInput::Hls(url), Some(300));
+        assert!(past_end.is_empty());
+    }
+
+    #[test]
+    fn reports_a_failed_seek_and_reads_on() {
+        // Served without range support, so the stream can't go back
+        let runtime = Runtime::new().unwrap();
+        let mp3 = std::fs::read(fixture("sine.mp3")).unwrap();
+        let url = format!("{}/sine.mp3", serve(&runtime, vec![("sine.mp3".to_string(), mp3)]));
+        let (sender, receiver) = mpsc::sync_channel(4);
+        let (seeks, seek_receiver) = mpsc::channel();
+        let handle = runtime.handle().clone();
+        let passthrough = Arc::new(AtomicBool::new(false));
+        seeks.send((1, 200)).unwrap();
+        let decoder = std::thread::spawn(move || {
+            decode(StreamInput::Http(url), handle, ResamplingQuality::High, passthrough, seek_receiver, sender)
+        });
+
+        let mut seeked = Vec::new();
+        let mut samples = 0;
+        for chunk in receiver.iter() {
+            match chunk.unwrap() {
+                StreamChunk::Seeked(id, result) => {
+                    seeked.push((id, result.is_ok()));
+                    if id == 1 {
+                        seeks.send((2, 0)).unwrap();
+                    }
+                }
+                StreamChunk::Pcm(pcm) if !seeked.is_empty() => samples += pcm.len(),
+                StreamChunk::End if seeked.len() == 2 => break,
+                _ => {}
+            }
+        }
+        drop(seeks);
+        decoder.join().unwrap();
+
+        assert_eq!(seeked, [(1, true), (2, false)]);
+        // Everything after the first seek, none of it repeated by the failed one
+        let seconds = (samples / CHANNELS) as f64 / SAMPLE_RATE as f64;
+        assert!((seconds - (0.2612 - 0.2)).abs() < 0.005, "{:.3}s after the seek", seconds);
+    }
+}
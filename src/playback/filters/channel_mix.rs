@@ -1,4 +1,5 @@
-use super::dsp::clamp_16_bit::clamp_16_bit;
+use serde_json::Value;
+use super::{get_f32, AudioFilter};
 
 pub struct ChannelMix {
     pub priority: u32,
@@ -18,40 +19,45 @@ impl ChannelMix {
             right_to_right: 1.0,
         }
     }
+}
 
-    pub fn update(
-        &mut self,
-        left_to_left: Option<f32>,
-        left_to_right: Option<f32>,
-        right_to_left: Option<f32>,
-        right_to_right: Option<f32>,
-    ) {
-        self.left_to_left = left_to_left.unwrap_or(1.0).clamp(0.0, 1.0);
-        self.left_to_right = left_to_right.unwrap_or(0.0).clamp(0.0, 1.0);
-        self.right_to_left = right_to_left.unwrap_or(0.0).clamp(0.0, 1.0);
-        self.right_to_right = right_to_right.unwrap_or(1.0).clamp(0.0, 1.0);
+impl AudioFilter for ChannelMix {
+    fn name(&self) -> &'static str {
+        "channelMix"
     }
 
-    pub fn process(&self, chunk: &mut [i16]) {
-        if self.left_to_left == 1.0
-            && self.left_to_right == 0.0
-            && self.right_to_left == 0.0
-            && self.right_to_right == 1.0
-        {
-            return;
-        }
+    fn priority(&self) -> u32 {
+        self.priority
+    }
+
+    fn update(&mut self, config: &Value) {
+        self.left_to_left = get_f32(config, "leftToLeft").unwrap_or(1.0).clamp(0.0, 1.0);
+        self.left_to_right = get_f32(config, "leftToRight").unwrap_or(0.0).clamp(0.0, 1.0);
+        self.right_to_left = get_f32(config, "rightToLeft").unwrap_or(0.0).clamp(0.0, 1.0);
+        self.right_to_right = get_f32(config, "rightToRight").unwrap_or(1.0).clamp(0.0, 1.0);
+    }
 
-        for frame in chunk.chunks_exact_mut(2) {
-            let current_left_sample = frame[0] as f32;
-            let current_right_sample = frame[1] as f32;
+    fn process(&mut self, buffer: &mut Vec<f32>) {
+        for frame in buffer.chunks_exact_mut(2) {
+            let current_left_sample = frame[0];
+            let current_right_sample = frame[1];
 
             let new_left_sample = current_left_sample * self.left_to_left
                 + current_right_sample * self.right_to_left;
             let new_right_sample = current_left_sample * self.left_to_right
                 + current_right_sample * self.right_to_right;
 
-            frame[0] = clamp_16_bit(new_left_sample);
-            frame[1] = clamp_16_bit(new_right_sample);
+            frame[0] = new_left_sample;
+            frame[1] = new_right_sample;
         }
     }
+
+    fn reset(&mut self) {}
+
+    fn is_enabled(&self) -> bool {
+        !(self.left_to_left == 1.0
+            && self.left_to_right == 0.0
+            && self.right_to_left == 0.0
+            && self.right_to_right == 1.0)
+    }
 }
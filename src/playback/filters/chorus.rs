@@ -1,9 +1,11 @@
+use serde_json::Value;
 use crate::playback::SAMPLE_RATE;
-use super::dsp::clamp_16_bit::clamp_16_bit;
+use super::dsp::clamp_16_bit::clamp_16_bit_range;
 use super::dsp::delay::DelayLine;
 use super::dsp::lfo::Lfo;
 use super::dsp::waves::Waveform;
 use std::f32::consts::PI;
+use super::{get_f32, AudioFilter};
 
 const MAX_DELAY_MS: f32 = 50.0;
 
@@ -60,13 +62,23 @@ impl Chorus {
             feedback: 0.0,
         }
     }
+}
+
+impl AudioFilter for Chorus {
+    fn name(&self) -> &'static str {
+        "chorus"
+    }
 
-    pub fn update(&mut self, rate: Option<f32>, depth: Option<f32>, delay: Option<f32>, mix: Option<f32>, feedback: Option<f32>) {
-        self.rate = rate.unwrap_or(0.0);
-        self.depth = depth.unwrap_or(0.0).clamp(0.0, 1.0);
-        self.delay = delay.unwrap_or(25.0).clamp(1.0, MAX_DELAY_MS - 5.0);
-        self.mix = mix.unwrap_or(0.5).clamp(0.0, 1.0);
-        self.feedback = feedback.unwrap_or(0.0).clamp(0.0, 0.95);
+    fn priority(&self) -> u32 {
+        self.priority
+    }
+
+    fn update(&mut self, config: &Value) {
+        self.rate = get_f32(config, "rate").unwrap_or(0.0);
+        self.depth = get_f32(config, "depth").unwrap_or(0.0).clamp(0.0, 1.0);
+        self.delay = get_f32(config, "delay").unwrap_or(25.0).clamp(1.0, MAX_DELAY_MS - 5.0);
+        self.mix = get_f32(config, "mix").unwrap_or(0.5).clamp(0.0, 1.0);
+        self.feedback = get_f32(config, "feedback").unwrap_or(0.0).clamp(0.0, 0.95);
 
         let rate2 = self.rate * 1.1;
 
@@ -76,32 +88,28 @@ impl Chorus {
         self.lfos[3].update(rate2, self.depth);
     }
 
-    pub fn process(&mut self, chunk: &mut [i16]) {
-        if self.rate == 0.0 || self.depth == 0.0 || self.mix == 0.0 {
-            return;
-        }
-
+    fn process(&mut self, buffer: &mut Vec<f32>) {
         let delay_width = self.depth * (SAMPLE_RATE * 0.004);
         let center_delay_samples = self.delay * (SAMPLE_RATE / 1000.0);
         let center_delay_samples2 = center_delay_samples * 1.2;
 
-        for frame in chunk.chunks_exact_mut(2) {
-            let left_sample = frame[0] as f32;
-            let right_sample = frame[1] as f32;
+        for frame in buffer.chunks_exact_mut(2) {
+            let left_sample = frame[0];
+            let right_sample = frame[1];
 
             let lfo1_l = self.lfos[0].get_value();
             let lfo1_r = self.lfos[1].get_value();
             let delay1_l = center_delay_samples + lfo1_l * delay_width;
             let delay1_r = center_delay_samples + lfo1_r * delay_width;
-            let delayed1_l = self.delays[0].read(delay1_l) as f32;
-            let delayed1_r = self.delays[1].read(delay1_r) as f32;
+            let delayed1_l = self.delays[0].read(delay1_l);
+            let delayed1_r = self.delays[1].read(delay1_r);
 
             let lfo2_l = self.lfos[2].get_value();
             let lfo2_r = self.lfos[3].get_value();
             let delay2_l = center_delay_samples2 + lfo2_l * delay_width;
             let delay2_r = center_delay_samples2 + lfo2_r * delay_width;
-            let delayed2_l = self.delays[2].read(delay2_l) as f32;
-            let delayed2_r = self.delays[3].read(delay2_r) as f32;
+            let delayed2_l = self.delays[2].read(delay2_l);
+            let delayed2_r = self.delays[3].read(delay2_r);
 
             let wet_left = (delayed1_l + delayed2_l) * 0.5;
             let wet_right = (delayed1_r + delayed2_r) * 0.5;
@@ -109,13 +117,26 @@ impl Chorus {
             let final_left = left_sample * (1.0 - self.mix) + wet_left * self.mix;
             let final_right = right_sample * (1.0 - self.mix) + wet_right * self.mix;
 
-            self.delays[0].write(clamp_16_bit(left_sample + delayed1_l * self.feedback));
-            self.delays[1].write(clamp_16_bit(right_sample + delayed1_r * self.feedback));
-            self.delays[2].write(clamp_16_bit(left_sample + delayed2_l * self.feedback));
-            self.delays[3].write(clamp_16_bit(right_sample + delayed2_r * self.feedback));
+            self.delays[0].write(clamp_16_bit_range(left_sample + delayed1_l * self.feedback));
+            self.delays[1].write(clamp_16_bit_range(right_sample + delayed1_r * self.feedback));
+            self.delays[2].write(clamp_16_bit_range(left_sample + delayed2_l * self.feedback));
+            self.delays[3].write(clamp_16_bit_range(right_sample + delayed2_r * self.feedback));
 
-            frame[0] = clamp_16_bit(final_left);
-            frame[1] = clamp_16_bit(final_right);
+            frame[0] = final_left;
+            frame[1] = final_right;
         }
     }
+
+    fn reset(&mut self) {
+        for lfo in &mut self.lfos {
+            lfo.reset();
+        }
+        for delay in &mut self.delays {
+            delay.clear();
+        }
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.rate != 0.0 && self.depth != 0.0 && self.mix != 0.0
+    }
 }
@@ -1,5 +1,6 @@
+use serde_json::Value;
 use crate::playback::SAMPLE_RATE;
-use super::dsp::clamp_16_bit::clamp_16_bit;
+use super::{get_f32, AudioFilter};
 
 fn db_to_linear(db: f32) -> f32 {
     10.0_f32.powf(db / 20.0)
@@ -41,20 +42,23 @@ impl Compressor {
             envelope: 0.0,
         }
     }
+}
+
+impl AudioFilter for Compressor {
+    fn name(&self) -> &'static str {
+        "compressor"
+    }
 
-    pub fn update(
-        &mut self,
-        threshold: Option<f32>,
-        ratio: Option<f32>,
-        attack: Option<f32>,
-        release: Option<f32>,
-        gain: Option<f32>,
-    ) {
-        self.threshold = threshold.unwrap_or(0.0);
-        self.ratio = ratio.unwrap_or(1.0);
-        self.attack = attack.unwrap_or(0.0);
-        self.release = release.unwrap_or(0.0);
-        self.gain = gain.unwrap_or(0.0);
+    fn priority(&self) -> u32 {
+        self.priority
+    }
+
+    fn update(&mut self, config: &Value) {
+        self.threshold = get_f32(config, "threshold").unwrap_or(0.0);
+        self.ratio = get_f32(config, "ratio").unwrap_or(1.0);
+        self.attack = get_f32(config, "attack").unwrap_or(0.0);
+        self.release = get_f32(config, "release").unwrap_or(0.0);
+        self.gain = get_f32(config, "gain").unwrap_or(0.0);
 
         self.attack_coeff = if self.attack > 0.0 {
             (-1.0 / ((self.attack / 1000.0) * SAMPLE_RATE)).exp()
@@ -71,14 +75,10 @@ impl Compressor {
         self.makeup_gain_linear = db_to_linear(self.gain);
     }
 
-    pub fn process(&mut self, chunk: &mut [i16]) {
-        if self.threshold == 0.0 && self.ratio == 1.0 && self.gain == 0.0 {
-            return;
-        }
-
-        for frame in chunk.chunks_exact_mut(2) {
-            let left_sample = frame[0] as f32;
-            let right_sample = frame[1] as f32;
+    fn process(&mut self, buffer: &mut Vec<f32>) {
+        for frame in buffer.chunks_exact_mut(2) {
+            let left_sample = frame[0];
+            let right_sample = frame[1];
 
             let peak = left_sample.abs().max(right_sample.abs());
 
@@ -100,8 +100,16 @@ impl Compressor {
             let new_left = left_sample * target_gain_linear;
             let new_right = right_sample * target_gain_linear;
 
-            frame[0] = clamp_16_bit(new_left);
-            frame[1] = clamp_16_bit(new_right);
+            frame[0] = new_left;
+            frame[1] = new_right;
         }
     }
+
+    fn reset(&mut self) {
+        self.envelope = 0.0;
+    }
+
+    fn is_enabled(&self) -> bool {
+        !(self.threshold == 0.0 && self.ratio == 1.0 && self.gain == 0.0)
+    }
 }
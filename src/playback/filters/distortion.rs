@@ -1,5 +1,6 @@
-use super::dsp::clamp_16_bit::clamp_16_bit;
+use serde_json::Value;
 use std::f32::consts::PI;
+use super::{get_f32, AudioFilter};
 
 const MAX_INT_16: f32 = 32767.0;
 
@@ -29,41 +30,32 @@ impl Distortion {
             scale: 1.0,
         }
     }
+}
 
-    pub fn update(
-        &mut self,
-        sin_offset: Option<f32>,
-        sin_scale: Option<f32>,
-        cos_offset: Option<f32>,
-        cos_scale: Option<f32>,
-        tan_offset: Option<f32>,
-        tan_scale: Option<f32>,
-        offset: Option<f32>,
-        scale: Option<f32>,
-    ) {
-        self.sin_offset = sin_offset.unwrap_or(0.0);
-        self.sin_scale = sin_scale.unwrap_or(0.0);
-        self.cos_offset = cos_offset.unwrap_or(0.0);
-        self.cos_scale = cos_scale.unwrap_or(0.0);
-        self.tan_offset = tan_offset.unwrap_or(0.0);
-        self.tan_scale = tan_scale.unwrap_or(0.0);
-        self.offset = offset.unwrap_or(0.0);
-        self.scale = scale.unwrap_or(1.0);
+impl AudioFilter for Distortion {
+    fn name(&self) -> &'static str {
+        "distortion"
     }
 
-    pub fn process(&self, chunk: &mut [i16]) {
-        if self.sin_scale == 0.0
-            && self.cos_scale == 0.0
-            && self.tan_scale == 0.0
-            && self.offset == 0.0
-            && self.scale == 1.0
-        {
-            return;
-        }
+    fn priority(&self) -> u32 {
+        self.priority
+    }
+
+    fn update(&mut self, config: &Value) {
+        self.sin_offset = get_f32(config, "sinOffset").unwrap_or(0.0);
+        self.sin_scale = get_f32(config, "sinScale").unwrap_or(0.0);
+        self.cos_offset = get_f32(config, "cosOffset").unwrap_or(0.0);
+        self.cos_scale = get_f32(config, "cosScale").unwrap_or(0.0);
+        self.tan_offset = get_f32(config, "tanOffset").unwrap_or(0.0);
+        self.tan_scale = get_f32(config, "tanScale").unwrap_or(0.0);
+        self.offset = get_f32(config, "offset").unwrap_or(0.0);
+        self.scale = get_f32(config, "scale").unwrap_or(1.0);
+    }
 
-        for frame in chunk.chunks_exact_mut(2) {
-            let current_left_sample = frame[0] as f32;
-            let current_right_sample = frame[1] as f32;
+    fn process(&mut self, buffer: &mut Vec<f32>) {
+        for frame in buffer.chunks_exact_mut(2) {
+            let current_left_sample = frame[0];
+            let current_right_sample = frame[1];
 
             let normalized_left = current_left_sample / MAX_INT_16;
             let normalized_right = current_right_sample / MAX_INT_16;
@@ -98,8 +90,18 @@ impl Distortion {
             distorted_left = (distorted_left * self.scale + self.offset) * MAX_INT_16;
             distorted_right = (distorted_right * self.scale + self.offset) * MAX_INT_16;
 
-            frame[0] = clamp_16_bit(distorted_left);
-            frame[1] = clamp_16_bit(distorted_right);
+            frame[0] = distorted_left;
+            frame[1] = distorted_right;
         }
     }
+
+    fn reset(&mut self) {}
+
+    fn is_enabled(&self) -> bool {
+        !(self.sin_scale == 0.0
+            && self.cos_scale == 0.0
+            && self.tan_scale == 0.0
+            && self.offset == 0.0
+            && self.scale == 1.0)
+    }
 }
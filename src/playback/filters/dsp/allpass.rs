@@ -17,6 +17,11 @@ impl Allpass {
         self.a = a.clamp(-0.999, 0.999);
     }
 
+    pub fn reset(&mut self) {
+        self.x1 = 0.0;
+        self.y1 = 0.0;
+    }
+
     pub fn process(&mut self, sample: f32) -> f32 {
         let output = self.a * sample + self.x1 - self.a * self.y1;
 
@@ -1,3 +1,9 @@
 pub fn clamp_16_bit(sample: f32) -> i16 {
     sample.round().clamp(-32768.0, 32767.0) as i16
 }
+
+/// Limits a sample to the 16-bit range without rounding it, for feedback paths that would
+/// otherwise keep growing.
+pub fn clamp_16_bit_range(sample: f32) -> f32 {
+    sample.clamp(-32768.0, 32767.0)
+}
@@ -1,5 +1,5 @@
 pub struct DelayLine {
-    buffer: Vec<i16>,
+    buffer: Vec<f32>,
     size: usize,
     write_index: usize,
 }
@@ -7,18 +7,18 @@ pub struct DelayLine {
 impl DelayLine {
     pub fn new(size: usize) -> Self {
         Self {
-            buffer: vec![0; size],
+            buffer: vec![0.0; size],
             size,
             write_index: 0,
         }
     }
 
-    pub fn write(&mut self, sample: i16) {
+    pub fn write(&mut self, sample: f32) {
         self.buffer[self.write_index] = sample;
         self.write_index = (self.write_index + 1) % self.size;
     }
 
-    pub fn read(&self, delay_in_samples: f32) -> i16 {
+    pub fn read(&self, delay_in_samples: f32) -> f32 {
         let safe_delay = delay_in_samples
             .floor()
             .max(0.0)
@@ -29,6 +29,6 @@ impl DelayLine {
     }
 
     pub fn clear(&mut self) {
-        self.buffer.fill(0);
+        self.buffer.fill(0.0);
     }
 }
@@ -29,6 +29,15 @@ impl Lfo {
         self.depth = depth;
     }
 
+    /// Whether the LFO moves at all.
+    pub fn is_active(&self) -> bool {
+        self.frequency != 0.0 && self.depth != 0.0
+    }
+
+    pub fn reset(&mut self) {
+        self.phase = 0.0;
+    }
+
     pub fn get_value(&mut self) -> f32 {
         if self.frequency == 0.0 {
             return 0.0;
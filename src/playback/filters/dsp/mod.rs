@@ -3,3 +3,4 @@ pub mod clamp_16_bit;
 pub mod delay;
 pub mod lfo;
 pub mod waves;
+pub mod wsola;
@@ -0,0 +1,232 @@
+use std::f32::consts::PI;
+
+/// Segment length, 40ms. Consecutive segments overlap by half of it.
+const WINDOW: usize = 1920;
+const HOP: usize = WINDOW / 2;
+// How far a segment may move from its nominal position to line up with the previous one
+const SEEK_RANGE: usize = 480;
+// Offset step of the first search pass, which is then refined around its best match
+const COARSE_STEP: usize = 4;
+
+/// WSOLA (waveform similarity overlap-add) time stretcher for interleaved stereo.
+///
+/// Input is cut into overlapping segments taken `tempo` times further apart than they are
+/// laid out in the output. Each segment is shifted within a small range to where its waveform
+/// best continues the previous one, so the tempo changes without touching the pitch.
+pub struct Wsola {
+    tempo: f64,
+    window: Vec<f32>,
+    input: Vec<f32>,
+    // Nominal input frame of the next segment
+    position: f64,
+    // Input that followed the last segment, which the next one should line up with
+    continuation: Vec<f32>,
+    // Input frame right after the continuation
+    continuation_end: usize,
+    // Second half of the last segment, faded out and overlapped with the next one
+    tail: Vec<f32>,
+}
+
+impl Wsola {
+    pub fn new() -> Self {
+        let window = (0..WINDOW)
+            .map(|i| 0.5 - 0.5 * (2.0 * PI * i as f32 / WINDOW as f32).cos())
+            .collect();
+        Self {
+            tempo: 1.0,
+            window,
+            input: Vec::new(),
+            position: 0.0,
+            continuation: Vec::new(),
+            continuation_end: 0,
+            tail: Vec::new(),
+        }
+    }
+
+    pub fn set_tempo(&mut self, tempo: f64) {
+        self.tempo = tempo;
+    }
+
+    pub fn reset(&mut self) {
+        self.input.clear();
+        self.position = 0.0;
+        self.continuation.clear();
+        self.continuation_end = 0;
+        self.tail.clear();
+    }
+
+    /// Appends the input that hasn't been stretched yet to `output` as it is, carrying on from
+    /// the last segment, and starts over. Switches the stretch off without dropping audio.
+    pub fn flush(&mut self, output: &mut Vec<f32>) {
+        if self.tail.is_empty() {
+            output.extend_from_slice(&self.input);
+        } else {
+            output.extend_from_slice(&self.continuation);
+            output.extend_from_slice(self.input.get(self.continuation_end * 2..).unwrap_or_default());
+        }
+        self.reset();
+    }
+
+    /// Appends the stretched audio to `output`. Output lags the input by about a segment.
+    pub fn process(&mut self, chunk: &[f32], output: &mut Vec<f32>) {
+        self.input.extend_from_slice(chunk);
+
+        while self.position as usize + SEEK_RANGE + WINDOW <= self.input.len() / 2 {
+            let nominal = self.position as usize;
+            let start = if self.continuation.is_empty() { nominal } else { self.best_match(nominal) };
+            let segment = &self.input[start * 2..(start + WINDOW) * 2];
+            let (head, rest) = segment.split_at(HOP * 2);
+
+            if self.tail.is_empty() {
+                // Nothing to cross-fade the first segment with
+                output.extend_from_slice(head);
+            } else {
+                output.extend(
+                    self.tail
+                        .iter()
+                        .zip(head)
+                        .enumerate()
+                        .map(|(i, (tail, sample))| tail + sample * self.window[i / 2]),
+                );
+            }
+            self.tail = rest.iter().enumerate().map(|(i, sample)| sample * self.window[HOP + i / 2]).collect();
+            self.continuation = rest.to_vec();
+            self.continuation_end = start + WINDOW;
+
+            self.position += HOP as f64 * self.tempo;
+            // Drop the input no later segment can reach
+            let consumed = (self.position as usize).saturating_sub(SEEK_RANGE);
+            self.input.drain(..consumed * 2);
+            self.position -= consumed as f64;
+            self.continuation_end = self.continuation_end.saturating_sub(consumed);
+        }
+    }
+
+    /// Start frame within the seek range around `nominal` that best continues the last segment.
+    fn best_match(&self, nominal: usize) -> usize {
+        let first = nominal.saturating_sub(SEEK_RANGE);
+        let last = nominal + SEEK_RANGE;
+        let coarse = self.best_in((first..=last).step_by(COARSE_STEP), COARSE_STEP);
+        let fine = coarse.saturating_sub(COARSE_STEP - 1).max(first)..=(coarse + COARSE_STEP - 1).min(last);
+        self.best_in(fine, 1)
+    }
+
+    fn best_in(&self, candidates: impl Iterator<Item = usize>, stride: usize) -> usize {
+        let mut best = (f32::MIN, 0);
+        for start in candidates {
+            let similarity = self.similarity(start, stride);
+            if similarity > best.0 {
+                best = (similarity, start);
+            }
+        }
+        best.1
+    }
+
+    /// Cross-correlation of the downmixed candidate with the continuation, normalised by the
+    /// candidate's energy so loud passages don't win by level alone.
+    fn similarity(&self, start: usize, stride: usize) -> f32 {
+        let mut correlation = 0.0;
+        let mut energy = 0.0;
+        for i in (0..HOP).step_by(stride) {
+            let expected = self.continuation[i * 2] + self.continuation[i * 2 + 1];
+            let candidate = self.input[(start + i) * 2] + self.input[(start + i) * 2 + 1];
+            correlation += expected * candidate;
+            energy += candidate * candidate;
+        }
+        correlation / (energy + 1.0).sqrt()
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::playback::test_utils::{frequency, sine};
+
+    // One 20ms frame of the player, in stereo frames
+    const CHUNK: usize = 960;
+
+    /// Runs `input` through a stretcher at `tempo` in player sized chunks. Also returns how
+    /// much output there was once the first `settled` input frames were in.
+    fn stretch(input: &[f32], tempo: f64, settled: usize) -> (Vec<f32>, usize) {
+        let mut wsola = Wsola::new();
+        wsola.set_tempo(tempo);
+        let mut output = Vec::new();
+        let mut at_settled = 0;
+        for (i, chunk) in input.chunks(CHUNK * 2).enumerate() {
+            wsola.process(chunk, &mut output);
+            if (i + 1) * CHUNK == settled {
+                at_settled = output.len() / 2;
+            }
+        }
+        (output, at_settled)
+    }
+
+    #[test]
+    fn keeps_the_pitch_while_following_the_tempo() {
+        let input = sine(440.0, 48000 * 4);
+        for tempo in [0.75, 1.5, 2.5] {
+            let (output, settled) = stretch(&input, tempo, 48000);
+
+            let measured = frequency(&output);
+            assert!((measured / 440.0 - 1.0).abs() < 0.005, "tempo {}: {:.1}Hz", tempo, measured);
+
+            // Past the first second the output keeps pace with the input, whatever the stretcher holds back
+            let ratio = (48000 * 3) as f64 / (output.len() / 2 - settled) as f64;
+            assert!((ratio / tempo - 1.0).abs() < 0.01, "tempo {}: input went {:.3} times as fast", tempo, ratio);
+        }
+    }
+
+    #[test]
+    fn spaces_clicks_by_the_tempo() {
+        // A click every 250ms, far enough apart to be rhythm rather than pitch
+        const SPACING: usize = 12000;
+        let mut input = vec![0.0; 48000 * 4 * 2];
+        for click in input.chunks_mut(SPACING * 2) {
+            click[..2].fill(10000.0);
+        }
+
+        for tempo in [0.75, 1.25, 1.5] {
+            let (output, _) = stretch(&input, tempo, 48000);
+            // A click can land in the cross-fade between segments, so it may be quieter
+            // than the input and leave a faint echo
+            let clicks: Vec<usize> = output
+                .iter()
+                .step_by(2)
+                .enumerate()
+                .filter(|(_, sample)| sample.abs() > 4000.0)
+                .map(|(i, _)| i)
+                .collect();
+            assert_eq!(clicks.len(), input.len() / 2 / SPACING, "tempo {}: clicks at {:?}", tempo, clicks);
+
+            let expected = SPACING as f64 / tempo;
+            let mean = (clicks[clicks.len() - 1] - clicks[0]) as f64 / (clicks.len() - 1) as f64;
+            assert!((mean / expected - 1.0).abs() < 0.01, "tempo {}: clicks {:.0} frames apart", tempo, mean);
+            for pair in clicks.windows(2) {
+                let spacing = (pair[1] - pair[0]) as f64;
+                assert!((spacing - expected).abs() <= HOP as f64, "tempo {}: clicks at {:?}", tempo, clicks);
+            }
+        }
+    }
+
+    #[test]
+    fn flush_carries_on_from_the_last_segment() {
+        let input = sine(440.0, 48000);
+        let mut wsola = Wsola::new();
+        wsola.set_tempo(1.5);
+        let mut output = Vec::new();
+        wsola.process(&input, &mut output);
+        let stretched = output.len();
+        wsola.flush(&mut output);
+        assert!(output.len() > stretched + WINDOW * 2, "only {} samples flushed", output.len() - stretched);
+
+        // A 440Hz sine at this level moves at most 461 between samples
+        let left: Vec<f32> = output.iter().step_by(2).copied().collect();
+        let jump = left.windows(2).map(|pair| (pair[1] - pair[0]).abs()).fold(0.0, f32::max);
+        assert!(jump < 500.0, "jumped by {}", jump);
+
+        // Nothing is held back anymore
+        let mut after = Vec::new();
+        wsola.process(&[], &mut after);
+        assert!(after.is_empty());
+    }
+}
@@ -1,6 +1,8 @@
+use serde_json::Value;
 use crate::playback::SAMPLE_RATE;
-use super::dsp::clamp_16_bit::clamp_16_bit;
+use super::dsp::clamp_16_bit::clamp_16_bit_range;
 use super::dsp::delay::DelayLine;
+use super::{get_f32, AudioFilter};
 
 const MAX_DELAY_S: f32 = 5.0;
 
@@ -27,35 +29,50 @@ impl Echo {
             right_delay: DelayLine::new(buffer_size),
         }
     }
+}
 
-    pub fn update(&mut self, delay: Option<f32>, feedback: Option<f32>, mix: Option<f32>) {
-        self.delay = delay.unwrap_or(0.0).clamp(0.0, MAX_DELAY_S * 1000.0);
-        self.feedback = feedback.unwrap_or(0.0).clamp(0.0, 1.0);
-        self.mix = mix.unwrap_or(0.0).clamp(0.0, 1.0);
+impl AudioFilter for Echo {
+    fn name(&self) -> &'static str {
+        "echo"
+    }
 
-        self.delay_time_samples = self.delay * (SAMPLE_RATE / 1000.0);
+    fn priority(&self) -> u32 {
+        self.priority
     }
 
-    pub fn process(&mut self, chunk: &mut [i16]) {
-        if self.delay == 0.0 || self.mix == 0.0 {
-            return;
-        }
+    fn update(&mut self, config: &Value) {
+        self.delay = get_f32(config, "delay").unwrap_or(0.0).clamp(0.0, MAX_DELAY_S * 1000.0);
+        self.feedback = get_f32(config, "feedback").unwrap_or(0.0).clamp(0.0, 1.0);
+        self.mix = get_f32(config, "mix").unwrap_or(0.0).clamp(0.0, 1.0);
 
-        for frame in chunk.chunks_exact_mut(2) {
-            let left_sample = frame[0] as f32;
-            let right_sample = frame[1] as f32;
+        self.delay_time_samples = self.delay * (SAMPLE_RATE / 1000.0);
+    }
 
-            let delayed_left = self.left_delay.read(self.delay_time_samples) as f32;
-            let delayed_right = self.right_delay.read(self.delay_time_samples) as f32;
+    fn process(&mut self, buffer: &mut Vec<f32>) {
+        for frame in buffer.chunks_exact_mut(2) {
+            let left_sample = frame[0];
+            let right_sample = frame[1];
 
-            self.left_delay.write(clamp_16_bit(left_sample + delayed_left * self.feedback));
-            self.right_delay.write(clamp_16_bit(right_sample + delayed_right * self.feedback));
+            let delayed_left = self.left_delay.read(self.delay_time_samples);
+            let delayed_right = self.right_delay.read(self.delay_time_samples);
+
+            self.left_delay.write(clamp_16_bit_range(left_sample + delayed_left * self.feedback));
+            self.right_delay.write(clamp_16_bit_range(right_sample + delayed_right * self.feedback));
 
             let new_left = left_sample * (1.0 - self.mix) + delayed_left * self.mix;
             let new_right = right_sample * (1.0 - self.mix) + delayed_right * self.mix;
 
-            frame[0] = clamp_16_bit(new_left);
-            frame[1] = clamp_16_bit(new_right);
+            frame[0] = new_left;
+            frame[1] = new_right;
         }
     }
+
+    fn reset(&mut self) {
+        self.left_delay.clear();
+        self.right_delay.clear();
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.delay != 0.0 && self.mix != 0.0
+    }
 }
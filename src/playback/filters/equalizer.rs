@@ -1,7 +1,8 @@
+use serde_json::Value;
 use crate::playback::SAMPLE_RATE;
-use super::dsp::clamp_16_bit::clamp_16_bit;
 use crate::types::filters::BandSetting;
 use std::f32::consts::PI;
+use super::{get_f32, get_usize, AudioFilter};
 
 const BAND_FREQUENCIES: [f32; 15] = [
     25.0, 40.0, 63.0, 100.0, 160.0, 250.0, 400.0, 630.0, 1000.0, 1600.0, 2500.0, 4000.0, 6300.0,
@@ -35,6 +36,8 @@ pub struct Equalizer {
     pub priority: u32,
     filters_state: Vec<FilterState>,
     filters_coefficients: Vec<FilterCoefficients>,
+    // Whether any band has a gain
+    enabled: bool,
 }
 
 impl Equalizer {
@@ -43,6 +46,7 @@ impl Equalizer {
             priority: 10,
             filters_state: Vec::new(),
             filters_coefficients: Vec::new(),
+            enabled: false,
         }
     }
 
@@ -93,8 +97,31 @@ impl Equalizer {
             a2: a2 / a0,
         };
     }
+}
+
+impl AudioFilter for Equalizer {
+    fn name(&self) -> &'static str {
+        "equalizer"
+    }
+
+    fn priority(&self) -> u32 {
+        self.priority
+    }
+
+    fn update(&mut self, config: &Value) {
+        let bands: Vec<BandSetting> = config
+            .as_array()
+            .map(|bands| {
+                bands
+                    .iter()
+                    .map(|b| BandSetting {
+                        band: get_usize(b, "band").unwrap_or(0),
+                        gain: get_f32(b, "gain").unwrap_or(0.0),
+                    })
+                    .collect()
+            })
+            .unwrap_or_default();
 
-    pub fn update(&mut self, bands: &[BandSetting]) {
         if self.filters_state.is_empty() || self.filters_coefficients.is_empty() {
             self.init_filters();
         }
@@ -107,21 +134,20 @@ impl Equalizer {
              self.filters_state[i] = FilterState::default();
         }
 
-        for band_setting in bands {
+        for band_setting in &bands {
             if band_setting.band < BAND_FREQUENCIES.len() {
                 self.update_band_coefficients(band_setting.band, band_setting.gain);
             }
         }
+        self.enabled = bands
+            .iter()
+            .any(|setting| setting.band < BAND_FREQUENCIES.len() && setting.gain != 0.0);
     }
 
-    pub fn process(&mut self, chunk: &mut [i16]) {
-        if self.filters_state.is_empty() {
-            return;
-        }
-
-        for frame in chunk.chunks_exact_mut(2) {
-            let mut current_left_sample = frame[0] as f32;
-            let mut current_right_sample = frame[1] as f32;
+    fn process(&mut self, buffer: &mut Vec<f32>) {
+        for frame in buffer.chunks_exact_mut(2) {
+            let mut current_left_sample = frame[0];
+            let mut current_right_sample = frame[1];
 
             for b in 0..BAND_FREQUENCIES.len() {
                 let coeffs = &self.filters_coefficients[b];
@@ -152,8 +178,16 @@ impl Equalizer {
                 current_right_sample = new_right_sample;
             }
 
-            frame[0] = clamp_16_bit(current_left_sample);
-            frame[1] = clamp_16_bit(current_right_sample);
+            frame[0] = current_left_sample;
+            frame[1] = current_right_sample;
         }
     }
+
+    fn reset(&mut self) {
+        self.filters_state.fill(FilterState::default());
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.enabled
+    }
 }
@@ -1,6 +1,7 @@
+use serde_json::Value;
 use crate::playback::SAMPLE_RATE;
-use super::dsp::clamp_16_bit::clamp_16_bit;
 use std::f32::consts::PI;
+use super::{get_f32, AudioFilter};
 
 pub struct Karaoke {
     pub priority: u32,
@@ -83,39 +84,32 @@ impl Karaoke {
         self.a2 /= self.a0;
         self.a0 = 1.0;
     }
+}
+
+impl AudioFilter for Karaoke {
+    fn name(&self) -> &'static str {
+        "karaoke"
+    }
 
-    pub fn update(
-        &mut self,
-        level: Option<f32>,
-        mono_level: Option<f32>,
-        filter_band: Option<f32>,
-        filter_width: Option<f32>,
-    ) {
-        self.level = level.unwrap_or(0.0).clamp(0.0, 1.0);
-        self.mono_level = mono_level.unwrap_or(0.0).clamp(0.0, 1.0);
-        self.filter_band = filter_band.unwrap_or(0.0);
-        self.filter_width = filter_width.unwrap_or(0.0);
+    fn priority(&self) -> u32 {
+        self.priority
+    }
+
+    fn update(&mut self, config: &Value) {
+        self.level = get_f32(config, "level").unwrap_or(0.0).clamp(0.0, 1.0);
+        self.mono_level = get_f32(config, "monoLevel").unwrap_or(0.0).clamp(0.0, 1.0);
+        self.filter_band = get_f32(config, "filterBand").unwrap_or(0.0);
+        self.filter_width = get_f32(config, "filterWidth").unwrap_or(0.0);
 
         self.update_coefficients();
 
-        self.xl1 = 0.0;
-        self.xl2 = 0.0;
-        self.yl1 = 0.0;
-        self.yl2 = 0.0;
-        self.xr1 = 0.0;
-        self.xr2 = 0.0;
-        self.yr1 = 0.0;
-        self.yr2 = 0.0;
+        self.reset();
     }
 
-    pub fn process(&mut self, chunk: &mut [i16]) {
-        if self.level == 0.0 && self.mono_level == 0.0 {
-            return;
-        }
-
-        for frame in chunk.chunks_exact_mut(2) {
-            let mut current_left_sample = frame[0] as f32;
-            let mut current_right_sample = frame[1] as f32;
+    fn process(&mut self, buffer: &mut Vec<f32>) {
+        for frame in buffer.chunks_exact_mut(2) {
+            let mut current_left_sample = frame[0];
+            let mut current_right_sample = frame[1];
 
             if self.mono_level > 0.0 {
                 let mono = (current_left_sample + current_right_sample) / 2.0;
@@ -147,8 +141,23 @@ impl Karaoke {
                 current_right_sample = new_right_sample;
             }
 
-            frame[0] = clamp_16_bit(current_left_sample);
-            frame[1] = clamp_16_bit(current_right_sample);
+            frame[0] = current_left_sample;
+            frame[1] = current_right_sample;
         }
     }
+
+    fn reset(&mut self) {
+        self.xl1 = 0.0;
+        self.xl2 = 0.0;
+        self.yl1 = 0.0;
+        self.yl2 = 0.0;
+        self.xr1 = 0.0;
+        self.xr2 = 0.0;
+        self.yr1 = 0.0;
+        self.yr2 = 0.0;
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.level != 0.0 || self.mono_level != 0.0
+    }
 }
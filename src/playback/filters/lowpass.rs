@@ -1,4 +1,5 @@
-use super::dsp::clamp_16_bit::clamp_16_bit;
+use serde_json::Value;
+use super::{get_f32, AudioFilter};
 
 pub struct Lowpass {
     pub priority: u32,
@@ -18,9 +19,19 @@ impl Lowpass {
             prev_right_output: 0.0,
         }
     }
+}
+
+impl AudioFilter for Lowpass {
+    fn name(&self) -> &'static str {
+        "lowpass"
+    }
+
+    fn priority(&self) -> u32 {
+        self.priority
+    }
 
-    pub fn update(&mut self, smoothing: Option<f32>) {
-        let smoothing = smoothing.unwrap_or(0.0);
+    fn update(&mut self, config: &Value) {
+        let smoothing = get_f32(config, "smoothing").unwrap_or(0.0);
         if smoothing > 1.0 {
             self.smoothing = smoothing;
             self.smoothing_factor = 1.0 / smoothing;
@@ -28,27 +39,31 @@ impl Lowpass {
             self.smoothing = 0.0;
             self.smoothing_factor = 0.0;
         }
-        self.prev_left_output = 0.0;
-        self.prev_right_output = 0.0;
+        self.reset();
     }
 
-    pub fn process(&mut self, chunk: &mut [i16]) {
-        if self.smoothing <= 1.0 {
-            return;
-        }
-
-        for frame in chunk.chunks_exact_mut(2) {
-            let current_left_sample = frame[0] as f32;
+    fn process(&mut self, buffer: &mut Vec<f32>) {
+        for frame in buffer.chunks_exact_mut(2) {
+            let current_left_sample = frame[0];
             let new_left_sample = self.prev_left_output
                 + self.smoothing_factor * (current_left_sample - self.prev_left_output);
             self.prev_left_output = new_left_sample;
-            frame[0] = clamp_16_bit(new_left_sample);
+            frame[0] = new_left_sample;
 
-            let current_right_sample = frame[1] as f32;
+            let current_right_sample = frame[1];
             let new_right_sample = self.prev_right_output
                 + self.smoothing_factor * (current_right_sample - self.prev_right_output);
             self.prev_right_output = new_right_sample;
-            frame[1] = clamp_16_bit(new_right_sample);
+            frame[1] = new_right_sample;
         }
     }
+
+    fn reset(&mut self) {
+        self.prev_left_output = 0.0;
+        self.prev_right_output = 0.0;
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.smoothing > 1.0
+    }
 }
@@ -1,9 +1,10 @@
+use serde_json::Value;
 use crate::playback::SAMPLE_RATE;
 use super::dsp::allpass::Allpass;
-use super::dsp::clamp_16_bit::clamp_16_bit;
 use super::dsp::lfo::Lfo;
 use super::dsp::waves::Waveform;
 use std::f32::consts::PI;
+use super::{get_f32, get_usize, AudioFilter};
 
 const MAX_STAGES: usize = 12;
 
@@ -57,39 +58,36 @@ impl Phaser {
             last_right_feedback: 0.0,
         }
     }
+}
+
+impl AudioFilter for Phaser {
+    fn name(&self) -> &'static str {
+        "phaser"
+    }
 
-    pub fn update(
-        &mut self,
-        stages: Option<usize>,
-        rate: Option<f32>,
-        depth: Option<f32>,
-        feedback: Option<f32>,
-        mix: Option<f32>,
-        min_frequency: Option<f32>,
-        max_frequency: Option<f32>,
-    ) {
-        self.stages = stages.unwrap_or(4).clamp(2, MAX_STAGES);
-        self.rate = rate.unwrap_or(0.0);
-        self.depth = depth.unwrap_or(1.0).clamp(0.0, 1.0);
-        self.feedback = feedback.unwrap_or(0.0).clamp(0.0, 0.9);
-        self.mix = mix.unwrap_or(0.5).clamp(0.0, 1.0);
-        self.min_frequency = min_frequency.unwrap_or(100.0);
-        self.max_frequency = max_frequency.unwrap_or(2500.0);
+    fn priority(&self) -> u32 {
+        self.priority
+    }
+
+    fn update(&mut self, config: &Value) {
+        self.stages = get_usize(config, "stages").unwrap_or(4).clamp(2, MAX_STAGES);
+        self.rate = get_f32(config, "rate").unwrap_or(0.0);
+        self.depth = get_f32(config, "depth").unwrap_or(1.0).clamp(0.0, 1.0);
+        self.feedback = get_f32(config, "feedback").unwrap_or(0.0).clamp(0.0, 0.9);
+        self.mix = get_f32(config, "mix").unwrap_or(0.5).clamp(0.0, 1.0);
+        self.min_frequency = get_f32(config, "minFrequency").unwrap_or(100.0);
+        self.max_frequency = get_f32(config, "maxFrequency").unwrap_or(2500.0);
 
         self.left_lfo.update(self.rate, self.depth);
         self.right_lfo.update(self.rate, self.depth);
     }
 
-    pub fn process(&mut self, chunk: &mut [i16]) {
-        if self.rate == 0.0 || self.depth == 0.0 || self.mix == 0.0 {
-            return;
-        }
-
+    fn process(&mut self, buffer: &mut Vec<f32>) {
         let sweep_range = self.max_frequency - self.min_frequency;
 
-        for frame in chunk.chunks_exact_mut(2) {
-            let left_sample = frame[0] as f32;
-            let right_sample = frame[1] as f32;
+        for frame in buffer.chunks_exact_mut(2) {
+            let left_sample = frame[0];
+            let right_sample = frame[1];
 
             let left_lfo_value = (self.left_lfo.get_value() + 1.0) / 2.0;
             let right_lfo_value = (self.right_lfo.get_value() + 1.0) / 2.0;
@@ -119,8 +117,22 @@ impl Phaser {
             self.last_right_feedback = wet_right;
             let final_right = right_sample * (1.0 - self.mix) + wet_right * self.mix;
 
-            frame[0] = clamp_16_bit(final_left);
-            frame[1] = clamp_16_bit(final_right);
+            frame[0] = final_left;
+            frame[1] = final_right;
+        }
+    }
+
+    fn reset(&mut self) {
+        self.left_lfo.reset();
+        self.right_lfo.reset();
+        for filter in self.left_filters.iter_mut().chain(&mut self.right_filters) {
+            filter.reset();
         }
+        self.last_left_feedback = 0.0;
+        self.last_right_feedback = 0.0;
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.rate != 0.0 && self.depth != 0.0 && self.mix != 0.0
     }
 }
@@ -1,4 +1,6 @@
-use super::dsp::clamp_16_bit::clamp_16_bit;
+use serde_json::Value;
+use super::dsp::wsola::Wsola;
+use super::{get_f32, AudioFilter};
 
 fn cubic_interpolate(p0: f32, p1: f32, p2: f32, p3: f32, t: f32) -> f32 {
     let t2 = t * t;
@@ -15,8 +17,14 @@ pub struct Timescale {
     speed: f32,
     pitch: f32,
     rate: f32,
+    // Resampling factor, shifting pitch and tempo together
     final_rate: f32,
-    input_buffer: Vec<i16>,
+    // Tempo change left for the time stretch once resampling is accounted for
+    tempo: f32,
+    stretcher: Wsola,
+    input_buffer: Vec<f32>,
+    // Input frame the resampler continues from, fractional part included
+    input_pos: f64,
 }
 
 impl Timescale {
@@ -27,140 +35,250 @@ impl Timescale {
             pitch: 1.0,
             rate: 1.0,
             final_rate: 1.0,
+            tempo: 1.0,
+            stretcher: Wsola::new(),
             input_buffer: Vec::new(),
+            input_pos: 0.0,
         }
     }
 
-    pub fn update(&mut self, speed: Option<f32>, pitch: Option<f32>, rate: Option<f32>) {
-        self.speed = speed.unwrap_or(1.0);
-        self.pitch = pitch.unwrap_or(1.0);
-        self.rate = rate.unwrap_or(1.0);
+    /// Time-stretches and resamples `chunk`; the output length follows the speed and rate.
+    fn scale(&mut self, chunk: &[f32]) -> Vec<f32> {
+        if self.final_rate <= 0.0 || self.tempo <= 0.0 {
+            return Vec::new();
+        }
+
+        if is_unity(self.tempo) {
+            self.input_buffer.extend_from_slice(chunk);
+        } else {
+            self.stretcher.process(chunk, &mut self.input_buffer);
+        }
+
+        if is_unity(self.final_rate) {
+            return std::mem::take(&mut self.input_buffer);
+        }
+
+        let frames = self.input_buffer.len() / 2;
+        let mut output = Vec::with_capacity((frames as f32 / self.final_rate) as usize * 2 + 2);
+
+        // Stereo frames around the position; the first frame stands in for the one before it
+        let sample = |frame: usize, channel: usize| self.input_buffer[frame * 2 + channel];
+        loop {
+            let i1 = self.input_pos as usize;
+            if i1 + 2 >= frames {
+                break;
+            }
+            let frac = (self.input_pos - i1 as f64) as f32;
+            let i0 = i1.saturating_sub(1);
+
+            for channel in 0..2 {
+                let out = cubic_interpolate(
+                    sample(i0, channel),
+                    sample(i1, channel),
+                    sample(i1 + 1, channel),
+                    sample(i1 + 2, channel),
+                    frac,
+                );
+                output.push(out);
+            }
+            self.input_pos += self.final_rate as f64;
+        }
+
+        // Keep the frame before the position for the next interpolation
+        let consumed = (self.input_pos as usize).saturating_sub(1).min(frames);
+        self.input_buffer.drain(..consumed * 2);
+        self.input_pos -= consumed as f64;
+
+        output
+    }
+}
+
+impl AudioFilter for Timescale {
+    fn name(&self) -> &'static str {
+        "timescale"
+    }
 
-        self.final_rate = self.speed * self.pitch * self.rate;
+    fn priority(&self) -> u32 {
+        self.priority
     }
 
-    // Takes input slice, returns processed Vec.
-    pub fn process(&mut self, chunk: &[i16]) -> Vec<i16> {
-        if (self.final_rate - 1.0).abs() < f32::EPSILON {
-             // Pass through if rate is 1.0. 
-             // But we might have leftover input_buffer? 
-             // JS: "if (this.finalRate === 1.0) { return chunk }"
-             // It implies ignoring input_buffer if rate is 1?
-             // Or maybe input_buffer is empty if rate was 1?
-             // If rate changes dynamically, we should probably drain buffer.
-             // But following JS exactly:
-             return chunk.to_vec();
+    fn update(&mut self, config: &Value) {
+        self.speed = get_f32(config, "speed").unwrap_or(1.0);
+        self.pitch = get_f32(config, "pitch").unwrap_or(1.0);
+        self.rate = get_f32(config, "rate").unwrap_or(1.0);
+
+        // Resampling by pitch * rate speeds the track up by the same factor, so the stretch
+        // makes up the rest of the speed
+        self.final_rate = self.pitch * self.rate;
+        self.tempo = if self.pitch > 0.0 { self.speed / self.pitch } else { 1.0 };
+
+        self.stretcher.set_tempo(self.tempo as f64);
+        if is_unity(self.tempo) {
+            // Audio still held for the stretch goes on to the resampler
+            self.stretcher.flush(&mut self.input_buffer);
         }
-        
-        if self.final_rate == 0.0 {
-            return Vec::new();
+        if is_unity(self.final_rate) {
+            // Frames the resampler has moved past are already out
+            let consumed = (self.input_pos as usize).min(self.input_buffer.len() / 2);
+            self.input_buffer.drain(..consumed * 2);
+            self.input_pos = 0.0;
         }
+    }
+
+    fn process(&mut self, buffer: &mut Vec<f32>) {
+        *buffer = self.scale(buffer);
+    }
+
+    fn reset(&mut self) {
+        self.stretcher.reset();
+        self.input_buffer.clear();
+        self.input_pos = 0.0;
+    }
+
+    fn is_enabled(&self) -> bool {
+        !(is_unity(self.tempo) && is_unity(self.final_rate))
+    }
+
+    fn holds_audio(&self) -> bool {
+        !self.input_buffer.is_empty()
+    }
+
+    /// Pitch doesn't change the track time.
+    fn track_speed(&self) -> f32 {
+        self.speed * self.rate
+    }
+}
+
+fn is_unity(factor: f32) -> bool {
+    (factor - 1.0).abs() < f32::EPSILON
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::playback::test_utils::{frequency, sine, sweep, RATE};
+    use serde_json::json;
+
+    const CHUNK: usize = 960;
+
+    /// Feeds `input` in player sized chunks, returning the output of each.
+    fn run(timescale: &mut Timescale, input: &[f32]) -> Vec<Vec<f32>> {
+        input
+            .chunks(CHUNK * 2)
+            .map(|chunk| {
+                let mut buffer = chunk.to_vec();
+                timescale.process(&mut buffer);
+                buffer
+            })
+            .collect()
+    }
+
+    // Speed, pitch and rate
+    const SETTINGS: [(f32, f32, f32); 5] = [(1.3, 1.0, 1.0), (1.0, 1.25, 1.0), (1.0, 1.0, 0.8), (0.8, 1.2, 1.0), (1.2, 0.9, 1.1)];
 
-        self.input_buffer.extend_from_slice(chunk);
+    #[test]
+    fn shifts_the_pitch_by_pitch_times_rate() {
+        let input = sine(440.0, 48000 * 2);
+        for (speed, pitch, rate) in SETTINGS {
+            let mut timescale = Timescale::new();
+            timescale.update(&json!({ "speed": speed, "pitch": pitch, "rate": rate }));
+            let output = run(&mut timescale, &input).concat();
 
-        if self.input_buffer.len() < 16 { // JS: < 16 bytes -> 8 samples? No, readInt16LE takes 2 bytes. 16 bytes = 8 samples (4 stereo frames).
-             // JS uses Buffer.length which is bytes.
-             // Rust Vec<i16>. So 8 i16s = 16 bytes.
-             if self.input_buffer.len() < 8 {
-                 return Vec::new();
-             }
+            let expected = 440.0 * (pitch * rate) as f64;
+            let measured = frequency(&output);
+            assert!(
+                (measured / expected - 1.0).abs() < 0.01,
+                "speed {} pitch {} rate {}: {:.1}Hz, expected {:.1}Hz",
+                speed,
+                pitch,
+                rate,
+                measured,
+                expected
+            );
         }
-        
-        // JS: outputLength = Math.floor(this.inputBuffer.length / this.finalRate)
-        // inputBuffer.length is BYTES.
-        // outputLength is BYTES.
-        // So in samples (i16):
-        let input_samples = self.input_buffer.len(); // i16 count
-        // input_bytes = input_samples * 2
-        // output_bytes = floor(input_bytes / rate)
-        // output_samples = output_bytes / 2
-        
-        let output_samples_count = ((input_samples * 2) as f32 / self.final_rate).floor() as usize / 2;
-        
-        // JS: finalOutputLength = outputLength - (outputLength % 4)
-        // 4 bytes = 2 i16s = 1 stereo frame.
-        // So ensure output_samples is multiple of 2.
-        let final_output_samples = output_samples_count - (output_samples_count % 2);
-        
-        let mut output_buffer = Vec::with_capacity(final_output_samples);
-
-        let mut output_pos = 0; // in samples (i16 index)
-        
-        // JS loop uses outputPos (bytes).
-        // Rust loop uses output_pos (i16 index).
-        // 4 bytes = 2 samples.
-        
-        while output_pos < final_output_samples {
-             // JS: inputFrame = (outputPos / 4) * this.finalRate
-             // outputPos / 4 is the frame index (stereo pair).
-             // output_pos / 2 is the frame index.
-             
-             let input_frame = (output_pos / 2) as f32 * self.final_rate;
-             let i1 = input_frame.floor() as isize;
-             let frac = input_frame - i1 as f32;
-             
-             // Stereo frames. i1 is frame index.
-             // Sample index = i1 * 2.
-             
-             let p0_idx = i1 - 1;
-             let p1_idx = i1;
-             let p2_idx = i1 + 1;
-             let p3_idx = i1 + 2;
-             
-             // Check boundary: (p3_idx + 1) * 4 > buffer.length
-             // (p3_idx + 1) frames > buffer frames?
-             // buffer frames = len / 2.
-             // p3_idx is frame index.
-             
-             if (p3_idx + 1) * 2 > self.input_buffer.len() as isize {
-                 break;
-             }
-             
-             fn get_sample(buf: &[i16], frame_idx: isize, offset: usize) -> f32 {
-                 // frame_idx can be negative (p0_idx < 0)
-                 // JS: if p0_idx < 0 ? read(p1_idx) : read(p0_idx)
-                 let idx = if frame_idx < 0 {
-                     // p1_idx * 4 + offset. p1_idx = i1 = 0 if p0 < 0?
-                     // Usually p1_idx is valid if p0 is -1.
-                     (frame_idx + 1) * 2 + offset as isize
-                 } else {
-                     frame_idx * 2 + offset as isize
-                 };
-                 buf[idx as usize] as f32
-             }
-             
-             // Left
-             let p0_l = get_sample(&self.input_buffer, p0_idx, 0);
-             let p1_l = get_sample(&self.input_buffer, p1_idx, 0);
-             let p2_l = get_sample(&self.input_buffer, p2_idx, 0);
-             let p3_l = get_sample(&self.input_buffer, p3_idx, 0);
-             let out_l = cubic_interpolate(p0_l, p1_l, p2_l, p3_l, frac);
-             output_buffer.push(clamp_16_bit(out_l));
-             
-             // Right
-             let p0_r = get_sample(&self.input_buffer, p0_idx, 1);
-             let p1_r = get_sample(&self.input_buffer, p1_idx, 1);
-             let p2_r = get_sample(&self.input_buffer, p2_idx, 1);
-             let p3_r = get_sample(&self.input_buffer, p3_idx, 1);
-             let out_r = cubic_interpolate(p0_r, p1_r, p2_r, p3_r, frac);
-             output_buffer.push(clamp_16_bit(out_r));
-             
-             output_pos += 2;
+    }
+
+    #[test]
+    fn follows_a_sweep_by_its_track_position() {
+        // 300Hz gliding up to 1200Hz over 4s
+        const SECONDS: f64 = 4.0;
+        let input = sweep(300.0, 1200.0, (RATE * SECONDS) as usize);
+        let input_frequency = |seconds: f64| 300.0 + 900.0 * seconds / SECONDS;
+        // 50ms windows, short enough to follow the sweep
+        let window = (RATE * 0.05) as usize;
+
+        for (speed, pitch) in [(1.5, 1.0), (0.75, 1.0), (1.0, 1.25), (1.0, 0.8), (1.3, 0.9)] {
+            let mut timescale = Timescale::new();
+            timescale.update(&json!({ "speed": speed, "pitch": pitch }));
+            let output = run(&mut timescale, &input).concat();
+
+            // Speed only moves through the track faster, pitch shifts what plays there
+            for (i, part) in output.chunks_exact(window * 2).enumerate() {
+                let track_position = (i as f64 + 0.5) * 0.05 * speed;
+                let expected = input_frequency(track_position) * pitch;
+                let measured = frequency(part);
+                assert!(
+                    (measured / expected - 1.0).abs() < 0.02,
+                    "speed {} pitch {} at {:.2}s of the track: {:.1}Hz, expected {:.1}Hz",
+                    speed,
+                    pitch,
+                    track_position,
+                    measured,
+                    expected
+                );
+            }
         }
-        
-        // Consumed input bytes: Math.floor((outputPos / 4) * this.finalRate) * 4
-        // outputPos (bytes). output_pos (samples).
-        // frame_count = output_pos / 2
-        let consumed_frames = ((output_pos / 2) as f32 * self.final_rate).floor() as usize;
-        let consumed_samples = consumed_frames * 2;
-        
-        // Remove consumed samples
-        if consumed_samples < self.input_buffer.len() {
-            self.input_buffer.drain(0..consumed_samples);
-        } else {
-            self.input_buffer.clear();
+    }
+
+    #[test]
+    fn output_length_follows_speed_times_rate() {
+        let input = sine(440.0, 48000 * 4);
+        for (speed, pitch, rate) in SETTINGS {
+            let mut timescale = Timescale::new();
+            timescale.update(&json!({ "speed": speed, "pitch": pitch, "rate": rate }));
+            let outputs = run(&mut timescale, &input);
+
+            // Past the first second, so what the stretch and resampler hold back doesn't count
+            let settled: usize = outputs[50..].iter().map(|output| output.len() / 2).sum();
+            let ratio = (48000 * 3) as f64 / settled as f64;
+            let expected = (speed * rate) as f64;
+            assert!(
+                (ratio / expected - 1.0).abs() < 0.01,
+                "speed {} pitch {} rate {}: track went {:.3} times as fast",
+                speed,
+                pitch,
+                rate,
+                ratio
+            );
+            assert!((timescale.track_speed() as f64 - expected).abs() < 1e-6);
         }
-        
-        output_buffer
+    }
+
+    /// Plays `input` at 1.5 times the speed, then switches back to normal.
+    fn speed_up_then_reset(input: &[f32]) -> Vec<f32> {
+        let mut timescale = Timescale::new();
+        timescale.update(&json!({ "speed": 1.5 }));
+        let mut output = run(&mut timescale, input).concat();
+        timescale.update(&Value::Null);
+        assert!(!timescale.is_enabled());
+        let mut buffer = Vec::new();
+        timescale.process(&mut buffer);
+        output.extend(buffer);
+        output
+    }
+
+    #[test]
+    fn keeps_held_audio_when_the_tempo_returns_to_unity() {
+        let output = speed_up_then_reset(&sine(440.0, 48000));
+        assert!(output.len() / 2 >= 32000, "{} frames", output.len() / 2);
+        let left: Vec<f32> = output.iter().step_by(2).copied().collect();
+        let jump = left.windows(2).map(|pair| (pair[1] - pair[0]).abs()).fold(0.0, f32::max);
+        assert!(jump < 500.0, "jumped by {}", jump);
+
+        // A click in the last 20ms, which the stretch was still holding on to
+        let mut input = vec![0.0; 48000 * 2];
+        input[47990 * 2..47990 * 2 + 2].fill(10000.0);
+        let output = speed_up_then_reset(&input);
+        assert!(output.contains(&10000.0));
     }
 }
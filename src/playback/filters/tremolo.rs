@@ -1,6 +1,7 @@
-use super::dsp::clamp_16_bit::clamp_16_bit;
+use serde_json::Value;
 use super::dsp::lfo::Lfo;
 use super::dsp::waves::Waveform;
+use super::{get_f32, AudioFilter};
 
 pub struct Tremolo {
     pub priority: u32,
@@ -14,19 +15,26 @@ impl Tremolo {
             lfo: Lfo::new(Waveform::Sine, 0.0, 0.0),
         }
     }
+}
+
+impl AudioFilter for Tremolo {
+    fn name(&self) -> &'static str {
+        "tremolo"
+    }
+
+    fn priority(&self) -> u32 {
+        self.priority
+    }
 
-    pub fn update(&mut self, frequency: Option<f32>, depth: Option<f32>) {
-        let frequency = frequency.unwrap_or(0.0);
-        let depth = depth.unwrap_or(0.0).clamp(0.0, 1.0);
+    fn update(&mut self, config: &Value) {
+        let frequency = get_f32(config, "frequency").unwrap_or(0.0);
+        let depth = get_f32(config, "depth").unwrap_or(0.0).clamp(0.0, 1.0);
 
         self.lfo.update(frequency, depth);
     }
 
-    pub fn process(&mut self, chunk: &mut [i16]) {
-        // Need to check depth/freq.
-        // Assuming LFO works if updated.
-        
-        for frame in chunk.chunks_exact_mut(2) {
+    fn process(&mut self, buffer: &mut Vec<f32>) {
+        for frame in buffer.chunks_exact_mut(2) {
              // JS Tremolo loop: i += 2. Single loop over all samples?
              // "for (let i = 0; i < chunk.length; i += 2)"
              // "chunk.readInt16LE(i)" -> This is every sample, L then R.
@@ -39,13 +47,21 @@ impl Tremolo {
              // So it's correct to call it per sample if LFO is intended to run at sample rate.
              // In JS: "const newSample = sample * multiplier"
              
-             let left_sample = frame[0] as f32;
+             let left_sample = frame[0];
              let left_multiplier = self.lfo.process();
-             frame[0] = clamp_16_bit(left_sample * left_multiplier);
+             frame[0] = left_sample * left_multiplier;
              
-             let right_sample = frame[1] as f32;
+             let right_sample = frame[1];
              let right_multiplier = self.lfo.process();
-             frame[1] = clamp_16_bit(right_sample * right_multiplier);
+             frame[1] = right_sample * right_multiplier;
         }
     }
+
+    fn reset(&mut self) {
+        self.lfo.reset();
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.lfo.is_active()
+    }
 }
@@ -1,8 +1,9 @@
+use serde_json::Value;
 use crate::playback::SAMPLE_RATE;
-use super::dsp::clamp_16_bit::clamp_16_bit;
 use super::dsp::delay::DelayLine;
 use super::dsp::lfo::Lfo;
 use super::dsp::waves::Waveform;
+use super::{get_f32, AudioFilter};
 
 const MAX_DELAY_MS: f32 = 20.0;
 
@@ -25,26 +26,30 @@ impl Vibrato {
             depth: 0.0,
         }
     }
+}
 
-    pub fn update(&mut self, frequency: Option<f32>, depth: Option<f32>) {
-        let frequency = frequency.unwrap_or(0.0);
-        let depth = depth.unwrap_or(0.0).clamp(0.0, 2.0);
-        
-        self.depth = depth; // Stored for process check
-        self.lfo.update(frequency, depth);
+impl AudioFilter for Vibrato {
+    fn name(&self) -> &'static str {
+        "vibrato"
     }
 
-    pub fn process(&mut self, chunk: &mut [i16]) {
-        if self.depth == 0.0 {
-            self.left_delay.clear();
-            self.right_delay.clear();
-            return;
-        }
+    fn priority(&self) -> u32 {
+        self.priority
+    }
+
+    fn update(&mut self, config: &Value) {
+        let frequency = get_f32(config, "frequency").unwrap_or(0.0);
+        let depth = get_f32(config, "depth").unwrap_or(0.0).clamp(0.0, 2.0);
+
+        self.depth = depth;
+        self.lfo.update(frequency, depth);
+    }
 
+    fn process(&mut self, buffer: &mut Vec<f32>) {
         let max_delay_width = self.depth * (SAMPLE_RATE * 0.005);
         let center_delay = max_delay_width;
 
-        for frame in chunk.chunks_exact_mut(2) {
+        for frame in buffer.chunks_exact_mut(2) {
             let lfo_value = self.lfo.get_value();
             
             let delay = center_delay + lfo_value * max_delay_width;
@@ -52,12 +57,22 @@ impl Vibrato {
             let left_sample = frame[0];
             self.left_delay.write(left_sample);
             let delayed_left = self.left_delay.read(delay);
-            frame[0] = clamp_16_bit(delayed_left as f32);
+            frame[0] = delayed_left;
             
             let right_sample = frame[1];
             self.right_delay.write(right_sample);
             let delayed_right = self.right_delay.read(delay);
-            frame[1] = clamp_16_bit(delayed_right as f32);
+            frame[1] = delayed_right;
         }
     }
+
+    fn reset(&mut self) {
+        self.lfo.reset();
+        self.left_delay.clear();
+        self.right_delay.clear();
+    }
+
+    fn is_enabled(&self) -> bool {
+        self.lfo.is_active()
+    }
 }
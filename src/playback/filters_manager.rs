@@ -1,243 +1,223 @@
 use serde_json::Value;
 
-use super::filters::channel_mix::ChannelMix;
-use super::filters::chorus::Chorus;
-use super::filters::compressor::Compressor;
-use super::filters::distortion::Distortion;
-use super::filters::echo::Echo;
-use super::filters::equalizer::Equalizer;
-use crate::types::filters::BandSetting;
-use super::filters::highpass::Highpass;
-use super::filters::karaoke::Karaoke;
-use super::filters::lowpass::Lowpass;
-use super::filters::phaser::Phaser;
-use super::filters::rotation::Rotation;
-use super::filters::timescale::Timescale;
-use super::filters::tremolo::Tremolo;
-use super::filters::vibrato::Vibrato;
+use super::filters::dsp::clamp_16_bit::clamp_16_bit;
+use super::filters::{self, AudioFilter, FilterRegistry};
+use super::volume::Volume;
 
 pub struct FiltersManager {
-    timescale: Timescale,
-    tremolo: Tremolo,
-    vibrato: Vibrato,
-    lowpass: Lowpass,
-    highpass: Highpass,
-    rotation: Rotation,
-    karaoke: Karaoke,
-    distortion: Distortion,
-    channel_mix: ChannelMix,
-    equalizer: Equalizer,
-    chorus: Chorus,
-    compressor: Compressor,
-    echo: Echo,
-    phaser: Phaser,
+    // Every registered filter, sorted by priority
+    filters: Vec<Box<dyn AudioFilter>>,
+    // Indices of the enabled filters, in processing order
+    chain: Vec<usize>,
 }
 
 impl FiltersManager {
     pub fn new() -> Self {
+        Self::with_registry(&filters::registry())
+    }
+
+    pub fn with_registry(registry: &FilterRegistry) -> Self {
         Self {
-            timescale: Timescale::new(),
-            tremolo: Tremolo::new(),
-            vibrato: Vibrato::new(),
-            lowpass: Lowpass::new(),
-            highpass: Highpass::new(),
-            rotation: Rotation::new(),
-            karaoke: Karaoke::new(),
-            distortion: Distortion::new(),
-            channel_mix: ChannelMix::new(),
-            equalizer: Equalizer::new(),
-            chorus: Chorus::new(),
-            compressor: Compressor::new(),
-            echo: Echo::new(),
-            phaser: Phaser::new(),
+            filters: registry.create_all(),
+            chain: Vec::new(),
         }
     }
 
+    /// Applies a player's `filters` object. Filters missing from it are disabled.
     pub fn update(&mut self, options: &Value) {
-        let filters = options.get("filters").unwrap_or(options);
-
-        // Helper to get float
-        let get_f32 =
-            |obj: &Value, key: &str| obj.get(key).and_then(|v| v.as_f64()).map(|v| v as f32);
-        let get_usize =
-            |obj: &Value, key: &str| obj.get(key).and_then(|v| v.as_u64()).map(|v| v as usize);
-
-        // Timescale
-        if let Some(cfg) = filters.get("timescale") {
-            self.timescale.update(
-                get_f32(cfg, "speed"),
-                get_f32(cfg, "pitch"),
-                get_f32(cfg, "rate"),
-            );
-        }
-
-        // Tremolo
-        if let Some(cfg) = filters.get("tremolo") {
-            self.tremolo
-                .update(get_f32(cfg, "frequency"), get_f32(cfg, "depth"));
+        let settings = options.get("filters").unwrap_or(options);
+        for (index, filter) in self.filters.iter_mut().enumerate() {
+            filter.update(settings.get(filter.name()).unwrap_or(&Value::Null));
+            // Don't carry state over to the next time it's enabled
+            if !filter.is_enabled() && !filter.holds_audio() && self.chain.contains(&index) {
+                filter.reset();
+            }
         }
+        // A filter switched off stays in until the next update so it can play out what it holds
+        self.chain = (0..self.filters.len())
+            .filter(|&index| self.filters[index].is_enabled() || self.filters[index].holds_audio())
+            .collect();
+    }
 
-        // Vibrato
-        if let Some(cfg) = filters.get("vibrato") {
-            self.vibrato
-                .update(get_f32(cfg, "frequency"), get_f32(cfg, "depth"));
-        }
+    /// Whether any filter is set, i.e. audio has to be processed.
+    pub fn is_active(&self) -> bool {
+        !self.chain.is_empty()
+    }
 
-        // Lowpass
-        if let Some(cfg) = filters.get("lowpass") {
-            self.lowpass.update(get_f32(cfg, "smoothing"));
-        }
+    /// Track time covered per second of output, e.g. set by the timescale speed and rate.
+    pub fn track_speed(&self) -> f32 {
+        self.chain.iter().map(|&index| self.filters[index].track_speed()).product()
+    }
 
-        // Highpass
-        if let Some(cfg) = filters.get("highpass") {
-            self.highpass.update(get_f32(cfg, "smoothing"));
+    /// Drops audio and state the filters hold from before a seek.
+    pub fn reset(&mut self) {
+        for &index in &self.chain {
+            self.filters[index].reset();
         }
+    }
 
-        // Rotation
-        if let Some(cfg) = filters.get("rotation") {
-            self.rotation.update(get_f32(cfg, "rotationHz"));
+    /// Runs the chain and then `volume` on f32 samples in the 16-bit range, so only the final
+    /// conversion back clips and rounds.
+    pub fn process(&mut self, chunk: &[i16], volume: &mut Volume) -> Vec<i16> {
+        // Leave the audio untouched so it stays bit-exact
+        if self.chain.is_empty() && volume.is_unity() {
+            return chunk.to_vec();
         }
-
-        // Karaoke
-        if let Some(cfg) = filters.get("karaoke") {
-            self.karaoke.update(
-                get_f32(cfg, "level"),
-                get_f32(cfg, "monoLevel"),
-                get_f32(cfg, "filterBand"),
-                get_f32(cfg, "filterWidth"),
-            );
+        let mut buffer: Vec<f32> = chunk.iter().map(|&sample| sample as f32).collect();
+        for &index in &self.chain {
+            self.filters[index].process(&mut buffer);
         }
+        volume.process(&mut buffer);
+        buffer.into_iter().map(clamp_16_bit).collect()
+    }
+}
 
-        // Distortion
-        if let Some(cfg) = filters.get("distortion") {
-            self.distortion.update(
-                get_f32(cfg, "sinOffset"),
-                get_f32(cfg, "sinScale"),
-                get_f32(cfg, "cosOffset"),
-                get_f32(cfg, "cosScale"),
-                get_f32(cfg, "tanOffset"),
-                get_f32(cfg, "tanScale"),
-                get_f32(cfg, "offset"),
-                get_f32(cfg, "scale"),
-            );
-        }
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::playback::test_utils::thd_n;
+    use serde_json::json;
+
+    /// 20ms of a 440Hz sine, interleaved stereo.
+    fn chunk() -> Vec<i16> {
+        (0..960)
+            .flat_map(|i| {
+                let sample = (8000.0 * (2.0 * std::f64::consts::PI * 440.0 * i as f64 / 48000.0).sin()) as i16;
+                [sample, sample]
+            })
+            .collect()
+    }
 
-        // ChannelMix
-        if let Some(cfg) = filters.get("channelMix") {
-            self.channel_mix.update(
-                get_f32(cfg, "leftToLeft"),
-                get_f32(cfg, "leftToRight"),
-                get_f32(cfg, "rightToLeft"),
-                get_f32(cfg, "rightToRight"),
-            );
+    #[test]
+    fn switching_the_timescale_off_plays_out_what_it_held() {
+        let mut manager = FiltersManager::new();
+        manager.update(&json!({ "timescale": { "speed": 1.5 } }));
+        let mut volume = Volume::new(100);
+        for _ in 0..50 {
+            manager.process(&chunk(), &mut volume);
         }
 
-        // Equalizer
-        if let Some(cfg) = filters.get("equalizer") {
-            if let Some(bands_arr) = cfg.as_array() {
-                let bands: Vec<BandSetting> = bands_arr
-                    .iter()
-                    .map(|b| BandSetting {
-                        band: get_usize(b, "band").unwrap_or(0),
-                        gain: get_f32(b, "gain").unwrap_or(0.0),
-                    })
-                    .collect();
-                self.equalizer.update(&bands);
-            }
-        }
+        manager.update(&json!({}));
+        assert!(manager.is_active());
+        // The stretch holds a segment and its seek range, which come out ahead of the new chunk
+        let frames = manager.process(&chunk(), &mut volume).len() / 2;
+        assert!(frames >= 960 + 1920, "{} frames", frames);
 
-        // Chorus
-        if let Some(cfg) = filters.get("chorus") {
-            self.chorus.update(
-                get_f32(cfg, "rate"),
-                get_f32(cfg, "depth"),
-                get_f32(cfg, "delay"),
-                get_f32(cfg, "mix"),
-                get_f32(cfg, "feedback"),
-            );
-        }
+        manager.update(&json!({}));
+        assert!(!manager.is_active());
+    }
 
-        // Compressor
-        if let Some(cfg) = filters.get("compressor") {
-            self.compressor.update(
-                get_f32(cfg, "threshold"),
-                get_f32(cfg, "ratio"),
-                get_f32(cfg, "attack"),
-                get_f32(cfg, "release"),
-                get_f32(cfg, "gain"),
-            );
-        }
+    const TONE: f64 = 1000.0;
 
-        // Echo
-        if let Some(cfg) = filters.get("echo") {
-            self.echo.update(
-                get_f32(cfg, "delay"),
-                get_f32(cfg, "feedback"),
-                get_f32(cfg, "mix"),
-            );
-        }
+    fn tone(amplitude: f64, seconds: f64) -> Vec<i16> {
+        (0..(48000.0 * seconds) as usize)
+            .flat_map(|i| {
+                let sample = (amplitude * (2.0 * std::f64::consts::PI * TONE * i as f64 / 48000.0).sin()).round() as i16;
+                [sample, sample]
+            })
+            .collect()
+    }
 
-        // Phaser
-        if let Some(cfg) = filters.get("phaser") {
-            self.phaser.update(
-                get_usize(cfg, "stages"),
-                get_f32(cfg, "rate"),
-                get_f32(cfg, "depth"),
-                get_f32(cfg, "feedback"),
-                get_f32(cfg, "mix"),
-                get_f32(cfg, "minFrequency"),
-                get_f32(cfg, "maxFrequency"),
-            );
+    /// THD+N of `input` through `filters` and `volume`, and of the same with the chain's
+    /// output converted to i16 before the volume, as it used to be.
+    fn measure(filters: &Value, volume: u32, input: &[i16]) -> (f64, f64) {
+        let mut single = FiltersManager::new();
+        single.update(filters);
+        let mut single_volume = Volume::new(volume);
+        let mut twice = FiltersManager::new();
+        twice.update(filters);
+        let mut twice_volume = Volume::new(volume);
+
+        let (mut once_output, mut twice_output) = (Vec::new(), Vec::new());
+        for chunk in input.chunks(2 * 960) {
+            once_output.extend(single.process(chunk, &mut single_volume));
+            let mut buffer: Vec<f32> = twice.process(chunk, &mut Volume::new(100)).iter().map(|&s| s as f32).collect();
+            twice_volume.process(&mut buffer);
+            twice_output.extend(buffer.into_iter().map(clamp_16_bit));
         }
+        // Past the filters' attack
+        (thd_n(&once_output[2 * 4800..], TONE), thd_n(&twice_output[2 * 4800..], TONE))
     }
 
-    pub fn process(&mut self, chunk: &[i16]) -> Vec<i16> {
-        // Priority 1: Timescale
-        // It consumes input and produces output (possibly different size).
-        let mut buffer = self.timescale.process(chunk);
-
-        // Priority 10: The rest
-        // Applied in-place on the buffer.
-
-        // Tremolo
-        self.tremolo.process(&mut buffer);
-
-        // Vibrato
-        self.vibrato.process(&mut buffer);
-
-        // Lowpass
-        self.lowpass.process(&mut buffer);
-
-        // Highpass
-        self.highpass.process(&mut buffer);
-
-        // Rotation
-        self.rotation.process(&mut buffer);
-
-        // Karaoke
-        self.karaoke.process(&mut buffer);
+    #[test]
+    fn stacked_filters_only_clip_at_the_final_conversion() {
+        // About +4dB at 1kHz from two overlapping bands, behind a highpass well below it
+        let stack = json!({
+            "equalizer": [{ "band": 8, "gain": 0.25 }, { "band": 9, "gain": 0.15 }],
+            "highpass": { "smoothing": 100.0 }
+        });
+
+        // Within range all along, only the rounding is left (the 16-bit floor is about -93dB)
+        let (once, _) = measure(&stack, 100, &tone(16000.0, 1.0));
+        assert!(once < -90.0, "THD+N {:.1}dB", once);
+
+        // The boost goes past full scale and the volume brings it back. Converting in between
+        // clipped it, now nothing is lost.
+        let (once, twice) = measure(&stack, 60, &tone(30000.0, 1.0));
+        assert!(once < -90.0, "THD+N {:.1}dB", once);
+        assert!(twice > -30.0, "THD+N {:.1}dB with the conversion in between", twice);
+
+        // Still over full scale after the volume, so both clip, but less than before
+        let (once, twice) = measure(&stack, 80, &tone(30000.0, 1.0));
+        assert!(once < twice - 5.0, "THD+N {:.1}dB, {:.1}dB with the conversion in between", once, twice);
+    }
 
-        // Distortion
-        self.distortion.process(&mut buffer);
+    /// Scales the audio by `gain`, registered under two names and priorities.
+    struct Gain {
+        name: &'static str,
+        priority: u32,
+        gain: f32,
+    }
 
-        // ChannelMix
-        self.channel_mix.process(&mut buffer);
+    impl AudioFilter for Gain {
+        fn name(&self) -> &'static str {
+            self.name
+        }
 
-        // Equalizer
-        self.equalizer.process(&mut buffer);
+        fn priority(&self) -> u32 {
+            self.priority
+        }
 
-        // Chorus
-        self.chorus.process(&mut buffer);
+        fn update(&mut self, config: &Value) {
+            self.gain = filters::get_f32(config, "gain").unwrap_or(1.0);
+        }
 
-        // Compressor
-        self.compressor.process(&mut buffer);
+        fn process(&mut self, buffer: &mut Vec<f32>) {
+            buffer.iter_mut().for_each(|sample| *sample *= self.gain);
+        }
 
-        // Echo
-        self.echo.process(&mut buffer);
+        fn reset(&mut self) {}
 
-        // Phaser
-        self.phaser.process(&mut buffer);
+        fn is_enabled(&self) -> bool {
+            self.gain != 1.0
+        }
+    }
 
-        buffer
+    #[test]
+    fn runs_registered_filters_by_priority() {
+        let mut registry = FilterRegistry::built_in();
+        registry.register("pregain", || Box::new(Gain { name: "pregain", priority: 20, gain: 1.0 }));
+        registry.register("postgain", || Box::new(Gain { name: "postgain", priority: 10, gain: 1.0 }));
+        // Registering a name again replaces the filter without moving it
+        registry.register("pregain", || Box::new(Gain { name: "pregain", priority: 0, gain: 1.0 }));
+        let names = registry.names();
+        assert_eq!(names[..2], ["timescale", "tremolo"]);
+        assert!(names.ends_with(&["phaser", "pregain", "postgain"]), "{:?}", names);
+        assert_eq!(names.len(), FilterRegistry::built_in().names().len() + 2);
+
+        let mut manager = FiltersManager::with_registry(&registry);
+        manager.update(&json!({
+            "postgain": { "gain": 0.5 },
+            "tremolo": { "frequency": 2.0, "depth": 0.5 },
+            "timescale": { "speed": 1.5 },
+            "pregain": { "gain": 2.0 }
+        }));
+        // Ahead of timescale by priority; behind the built-in filters of the same priority
+        let chain: Vec<&str> = manager.chain.iter().map(|&index| manager.filters[index].name()).collect();
+        assert_eq!(chain, ["pregain", "timescale", "tremolo", "postgain"]);
+
+        manager.update(&json!({ "pregain": { "gain": 2.0 } }));
+        let output = manager.process(&[1000, -1000], &mut Volume::new(100));
+        assert_eq!(output, [2000, -2000]);
     }
 }
@@ -0,0 +1,62 @@
+use crate::types::stats::FrameStats;
+
+/// Frame slots in a minute at 20ms per frame.
+pub const EXPECTED_FRAMES_PER_MINUTE: u64 = 3000;
+
+/// Counts what happened in each 20ms slot of a player's playback: a frame was sent,
+/// nothing was ready (nulled), or the pacer fell behind and skipped it (deficit).
+/// Stats cover the last full minute of slots, so they don't depend on wall time.
+pub struct FrameCounter {
+    sent: u64,
+    nulled: u64,
+    deficit: u64,
+    last_minute: Option<FrameStats>,
+}
+
+impl FrameCounter {
+    pub fn new() -> Self {
+        Self {
+            sent: 0,
+            nulled: 0,
+            deficit: 0,
+            last_minute: None,
+        }
+    }
+
+    pub fn sent(&mut self) {
+        self.sent += 1;
+        self.roll();
+    }
+
+    pub fn nulled(&mut self) {
+        self.nulled += 1;
+        self.roll();
+    }
+
+    pub fn missed(&mut self, frames: u64) {
+        for _ in 0..frames {
+            self.deficit += 1;
+            self.roll();
+        }
+    }
+
+    /// Stats of the last full minute, or `None` if the player hasn't been playing for that long.
+    pub fn last_minute(&self) -> Option<FrameStats> {
+        self.last_minute.clone()
+    }
+
+    fn roll(&mut self) {
+        if self.sent + self.nulled + self.deficit < EXPECTED_FRAMES_PER_MINUTE {
+            return;
+        }
+        self.last_minute = Some(FrameStats {
+            sent: self.sent,
+            nulled: self.nulled,
+            deficit: self.deficit,
+            expected: EXPECTED_FRAMES_PER_MINUTE,
+        });
+        self.sent = 0;
+        self.nulled = 0;
+        self.deficit = 0;
+    }
+}
@@ -0,0 +1,309 @@
+use reqwest::Url;
+use std::collections::VecDeque;
+use std::io::{self, Read, Seek, SeekFrom};
+use std::path::Path;
+use symphonia::core::io::MediaSource;
+use symphonia::core::probe::Hint;
+use tokio::runtime::Handle;
+
+use super::http_stream::{HttpStream, CLIENT};
+
+/// Media playlist of an HLS stream, resolved from a master playlist if needed.
+///
+/// Only the segments listed when it was loaded are played, so live playlists stop there.
+pub struct HlsPlaylist {
+    handle: Handle,
+    // `EXT-X-MAP` section, such as the `moov` of fragmented MP4, sent ahead of the segments
+    init: Option<String>,
+    segments: Vec<Segment>,
+    duration: f64,
+}
+
+struct Segment {
+    url: String,
+    start: f64,
+}
+
+enum Playlist {
+    // Bandwidth and URL of each variant, and the URL of the default audio rendition
+    Master { variants: Vec<(u64, String)>, audio: Option<String> },
+    Media { init: Option<String>, segments: Vec<Segment>, duration: f64 },
+}
+
+impl HlsPlaylist {
+    pub fn load(url: &str, handle: Handle) -> Result<Self, String> {
+        let mut url = url.to_string();
+        // A master playlist only points to media playlists
+        for _ in 0..2 {
+            let base = Url::parse(&url).map_err(|e| format!("Invalid playlist URL: {}", e))?;
+            let text = handle.block_on(async {
+                let response = CLIENT.get(url.as_str()).send().await?.error_for_status()?;
+                response.text().await
+            });
+            let text = text.map_err(|e| format!("Failed to load playlist: {}", e))?;
+
+            match parse(&text, &base)? {
+                Playlist::Master { variants, audio } => {
+                    // Separate audio keeps video out of the download; otherwise the best variant
+                    url = match audio {
+                        Some(audio) => audio,
+                        None => variants
+                            .into_iter()
+                            .max_by_key(|(bandwidth, _)| *bandwidth)
+                            .map(|(_, url)| url)
+                            .ok_or("Master playlist has no variants")?,
+                    };
+                }
+                Playlist::Media { init, segments, duration } => {
+                    return Ok(Self { handle, init, segments, duration });
+                }
+            }
+        }
+        Err("Master playlist points to another master playlist".to_string())
+    }
+
+    /// Index of the segment playing at `seconds`, `None` past the end.
+    pub fn segment_at(&self, seconds: f64) -> Option<usize> {
+        if seconds >= self.duration {
+            return None;
+        }
+        Some(self.segments.partition_point(|segment| segment.start <= seconds).saturating_sub(1))
+    }
+
+    /// Time the segment at `index` starts at.
+    pub fn start_of(&self, index: usize) -> f64 {
+        self.segments.get(index).map_or(self.duration, |segment| segment.start)
+    }
+
+    /// The segments from `first` on as one stream, after the init section.
+    pub fn stream_from(&self, first: usize) -> HlsStream {
+        let urls = self
+            .init
+            .iter()
+            .chain(self.segments.iter().skip(first).map(|segment| &segment.url))
+            .cloned()
+            .collect();
+        HlsStream { handle: self.handle.clone(), urls, current: None, position: 0 }
+    }
+
+    /// Container hint from the init section or the first segment.
+    pub fn hint(&self) -> Hint {
+        let mut hint = Hint::new();
+        let url = self.init.as_ref().or(self.segments.first().map(|segment| &segment.url));
+        if let Some(extension) = url.and_then(|url| url_extension(url)) {
+            hint.with_extension(extension);
+        }
+        hint
+    }
+}
+
+/// Blocking reader over the segments of an HLS stream, requested one after another.
+/// It can't seek; seeks start a new stream from the segment with the target.
+pub struct HlsStream {
+    handle: Handle,
+    urls: VecDeque<String>,
+    current: Option<HttpStream>,
+    position: u64,
+}
+
+impl Read for HlsStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        loop {
+            if let Some(current) = &mut self.current {
+                let read = current.read(buf)?;
+                if read > 0 || buf.is_empty() {
+                    self.position += read as u64;
+                    return Ok(read);
+                }
+            }
+            let Some(url) = self.urls.pop_front() else {
+                self.current = None;
+                return Ok(0);
+            };
+            self.current = Some(HttpStream::open(&url, self.handle.clone()).map_err(io::Error::other)?);
+        }
+    }
+}
+
+impl Seek for HlsStream {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        match pos {
+            SeekFrom::Current(0) => Ok(self.position),
+            SeekFrom::Start(offset) if offset == self.position => Ok(offset),
+            _ => Err(io::Error::new(io::ErrorKind::Unsupported, "HLS streams are seeked by segment")),
+        }
+    }
+}
+
+impl MediaSource for HlsStream {
+    fn is_seekable(&self) -> bool {
+        false
+    }
+
+    fn byte_len(&self) -> Option<u64> {
+        None
+    }
+}
+
+fn parse(text: &str, base: &Url) -> Result<Playlist, String> {
+    let mut lines = text.lines().map(str::trim).filter(|line| !line.is_empty());
+    if lines.next() != Some("#EXTM3U") {
+        return Err("Not an HLS playlist".to_string());
+    }
+    let resolve = |uri: &str| {
+        base.join(uri)
+            .map(|url| url.to_string())
+            .map_err(|e| format!("Invalid URL {} in playlist: {}", uri, e))
+    };
+
+    let mut variants = Vec::new();
+    let mut audio = None;
+    let mut init = None;
+    let mut segments = Vec::new();
+    let mut duration = 0.0;
+    // Tag that applies to the next URI line
+    let mut pending_variant = None;
+    let mut pending_segment = None;
+
+    for line in lines {
+        if let Some(attributes) = line.strip_prefix("#EXT-X-STREAM-INF:") {
+            pending_variant = Some(attribute(attributes, "BANDWIDTH").and_then(|b| b.parse().ok()).unwrap_or(0));
+        } else if let Some(attributes) = line.strip_prefix("#EXT-X-MEDIA:") {
+            let is_default = attribute(attributes, "DEFAULT") == Some("YES");
+            if attribute(attributes, "TYPE") == Some("AUDIO")
+                && let Some(uri) = attribute(attributes, "URI")
+                && (audio.is_none() || is_default)
+            {
+                audio = Some(resolve(uri)?);
+            }
+        } else if let Some(attributes) = line.strip_prefix("#EXT-X-MAP:") {
+            let uri = attribute(attributes, "URI").ok_or("EXT-X-MAP without a URI")?;
+            init = Some(resolve(uri)?);
+        } else if let Some(attributes) = line.strip_prefix("#EXT-X-KEY:") {
+            if attribute(attributes, "METHOD") != Some("NONE") {
+                return Err("Encrypted HLS streams aren't supported".to_string());
+            }
+        } else if line.starts_with("#EXT-X-BYTERANGE") {
+            return Err("HLS byte range segments aren't supported".to_string());
+        } else if let Some(info) = line.strip_prefix("#EXTINF:") {
+            let length = info.split(',').next().unwrap_or(info);
+            pending_segment = Some(length.trim().parse::<f64>().map_err(|_| format!("Invalid segment duration {}", length))?);
+        } else if line.starts_with('#') {
+            continue;
+        } else if let Some(bandwidth) = pending_variant.take() {
+            variants.push((bandwidth, resolve(line)?));
+        } else if let Some(length) = pending_segment.take() {
+            let url = resolve(line)?;
+            if url_extension(&url) == Some("ts") {
+                return Err("HLS streams with MPEG-TS segments aren't supported".to_string());
+            }
+            segments.push(Segment { url, start: duration });
+            duration += length;
+        }
+    }
+
+    if !variants.is_empty() || audio.is_some() {
+        Ok(Playlist::Master { variants, audio })
+    } else if segments.is_empty() {
+        Err("Playlist has no segments".to_string())
+    } else {
+        Ok(Playlist::Media { init, segments, duration })
+    }
+}
+
+/// Value of `name` in an attribute list like `BANDWIDTH=128000,CODECS="mp4a.40.2"`.
+fn attribute<'a>(attributes: &'a str, name: &str) -> Option<&'a str> {
+    let mut rest = attributes;
+    while !rest.is_empty() {
+        let (key, value) = rest.split_once('=')?;
+        let (value, next) = match value.strip_prefix('"') {
+            Some(quoted) => {
+                let end = quoted.find('"')?;
+                (&quoted[..end], quoted[end + 1..].trim_start_matches(','))
+            }
+            None => value.split_once(',').unwrap_or((value, "")),
+        };
+        if key.trim() == name {
+            return Some(value);
+        }
+        rest = next;
+    }
+    None
+}
+
+fn url_extension(url: &str) -> Option<&str> {
+    let path = url.split(['?', '#']).next().unwrap_or(url);
+    Path::new(path).extension().and_then(|e| e.to_str())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn base() -> Url {
+        Url::parse("https://cdn.example.com/live/stream.m3u8").unwrap()
+    }
+
+    #[test]
+    fn picks_the_audio_rendition_or_the_best_variant() {
+        let master = "#EXTM3U\n\
+            #EXT-X-STREAM-INF:BANDWIDTH=64000,CODECS=\"mp4a.40.2\"\nlow.m3u8\n\
+            #EXT-X-STREAM-INF:BANDWIDTH=256000,CODECS=\"mp4a.40.2,avc1.4d401f\"\nhigh/index.m3u8\n";
+        let Ok(Playlist::Master { variants, audio: None }) = parse(master, &base()) else {
+            panic!("not parsed as a master playlist");
+        };
+        assert_eq!(
+            variants,
+            [
+                (64000, "https://cdn.example.com/live/low.m3u8".to_string()),
+                (256000, "https://cdn.example.com/live/high/index.m3u8".to_string()),
+            ]
+        );
+
+        let with_audio = format!(
+            "{}#EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"a\",NAME=\"en\",URI=\"audio/en.m3u8\"\n\
+             #EXT-X-MEDIA:TYPE=AUDIO,GROUP-ID=\"a\",NAME=\"de, main\",DEFAULT=YES,URI=\"/audio/de.m3u8\"\n",
+            master
+        );
+        let Ok(Playlist::Master { audio, .. }) = parse(&with_audio, &base()) else {
+            panic!("not parsed as a master playlist");
+        };
+        assert_eq!(audio.as_deref(), Some("https://cdn.example.com/audio/de.m3u8"));
+    }
+
+    #[test]
+    fn lays_segments_out_by_their_durations() {
+        let media = "#EXTM3U\n#EXT-X-TARGETDURATION:10\n#EXT-X-MAP:URI=\"init.mp4\"\n\
+            #EXTINF:10.0,\nseg0.m4s\n#EXTINF:9.5,title\nseg1.m4s?token=a\n#EXTINF:4,\nseg2.m4s\n#EXT-X-ENDLIST\n";
+        let Ok(Playlist::Media { init, segments, duration }) = parse(media, &base()) else {
+            panic!("not parsed as a media playlist");
+        };
+        let runtime = tokio::runtime::Runtime::new().unwrap();
+        let playlist = HlsPlaylist { handle: runtime.handle().clone(), init, segments, duration };
+
+        assert_eq!(playlist.duration, 23.5);
+        assert_eq!(playlist.segments[1].url, "https://cdn.example.com/live/seg1.m4s?token=a");
+        let starts: Vec<f64> = (0..3).map(|i| playlist.start_of(i)).collect();
+        assert_eq!(starts, [0.0, 10.0, 19.5]);
+
+        for (seconds, segment) in [(0.0, Some(0)), (9.99, Some(0)), (10.0, Some(1)), (19.6, Some(2)), (23.5, None)] {
+            assert_eq!(playlist.segment_at(seconds), segment, "{}s", seconds);
+        }
+
+        let stream = playlist.stream_from(2);
+        assert_eq!(
+            stream.urls,
+            ["https://cdn.example.com/live/init.mp4", "https://cdn.example.com/live/seg2.m4s"]
+        );
+    }
+
+    #[test]
+    fn rejects_what_it_cant_play() {
+        let encrypted = "#EXTM3U\n#EXT-X-KEY:METHOD=AES-128,URI=\"key\"\n#EXTINF:4,\na.aac\n";
+        let transport = "#EXTM3U\n#EXTINF:4,\na.ts\n";
+        let empty = "#EXTM3U\n#EXT-X-ENDLIST\n";
+        for playlist in [encrypted, transport, empty, "<html>"] {
+            assert!(parse(playlist, &base()).is_err(), "{}", playlist);
+        }
+    }
+}
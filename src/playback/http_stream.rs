@@ -0,0 +1,135 @@
+use once_cell::sync::Lazy;
+use reqwest::header::{ACCEPT_RANGES, CONTENT_LENGTH, CONTENT_TYPE, RANGE};
+use reqwest::{Client, Response, StatusCode};
+use std::io::{self, Read, Seek, SeekFrom};
+use std::sync::Mutex;
+use symphonia::core::io::MediaSource;
+use tokio::runtime::Handle;
+
+pub static CLIENT: Lazy<Client> = Lazy::new(Client::new);
+
+/// Blocking `Read + Seek` over an HTTP body, for use on the decoder thread.
+/// Seeking re-requests the body from the new offset with a `Range` header.
+pub struct HttpStream {
+    url: String,
+    handle: Handle,
+    // Only accessed through `&mut self`; the Mutex makes the stream `Sync` for symphonia.
+    response: Mutex<Option<Response>>,
+    chunk: Vec<u8>,
+    chunk_offset: usize,
+    position: u64,
+    length: Option<u64>,
+    seekable: bool,
+    pub content_type: Option<String>,
+}
+
+impl HttpStream {
+    pub fn open(url: &str, handle: Handle) -> Result<Self, String> {
+        let response = handle
+            .block_on(CLIENT.get(url).send())
+            .map_err(|e| format!("Failed to request stream: {}", e))?;
+        if !response.status().is_success() {
+            return Err(format!("Stream responded with status {}", response.status()));
+        }
+
+        let header = |name| response.headers().get(name).and_then(|v| v.to_str().ok());
+        let length = header(CONTENT_LENGTH).and_then(|v| v.parse().ok());
+        let seekable = length.is_some() && header(ACCEPT_RANGES) == Some("bytes");
+        let content_type = header(CONTENT_TYPE).map(|v| v.to_string());
+
+        Ok(Self {
+            url: url.to_string(),
+            handle,
+            response: Mutex::new(Some(response)),
+            chunk: Vec::new(),
+            chunk_offset: 0,
+            position: 0,
+            length,
+            seekable,
+            content_type,
+        })
+    }
+
+    fn request_from(&mut self, offset: u64) -> io::Result<()> {
+        let response = self
+            .handle
+            .block_on(CLIENT.get(&self.url).header(RANGE, format!("bytes={}-", offset)).send())
+            .map_err(io::Error::other)?;
+        if response.status() != StatusCode::PARTIAL_CONTENT {
+            return Err(io::Error::other(format!(
+                "Range request responded with status {}",
+                response.status()
+            )));
+        }
+
+        *self.response.get_mut().unwrap() = Some(response);
+        self.chunk.clear();
+        self.chunk_offset = 0;
+        self.position = offset;
+        Ok(())
+    }
+}
+
+impl Read for HttpStream {
+    fn read(&mut self, buf: &mut [u8]) -> io::Result<usize> {
+        while self.chunk_offset >= self.chunk.len() {
+            let Some(response) = self.response.get_mut().unwrap().as_mut() else {
+                return Ok(0);
+            };
+            match self.handle.block_on(response.chunk()).map_err(io::Error::other)? {
+                Some(chunk) => {
+                    self.chunk = chunk.to_vec();
+                    self.chunk_offset = 0;
+                }
+                None => {
+                    *self.response.get_mut().unwrap() = None;
+                    return Ok(0);
+                }
+            }
+        }
+
+        let available = &self.chunk[self.chunk_offset..];
+        let read = available.len().min(buf.len());
+        buf[..read].copy_from_slice(&available[..read]);
+        self.chunk_offset += read;
+        self.position += read as u64;
+        Ok(read)
+    }
+}
+
+impl Seek for HttpStream {
+    fn seek(&mut self, pos: SeekFrom) -> io::Result<u64> {
+        let target = match pos {
+            SeekFrom::Start(offset) => Some(offset),
+            SeekFrom::Current(delta) => self.position.checked_add_signed(delta),
+            SeekFrom::End(delta) => self.length.and_then(|l| l.checked_add_signed(delta)),
+        }
+        .ok_or_else(|| io::Error::new(io::ErrorKind::InvalidInput, "Invalid seek position"))?;
+
+        if target == self.position {
+            return Ok(target);
+        }
+        // Short forward seeks are cheaper to read through than to re-request
+        let remaining = (self.chunk.len() - self.chunk_offset) as u64;
+        if target > self.position && target - self.position <= remaining {
+            self.chunk_offset += (target - self.position) as usize;
+            self.position = target;
+            return Ok(target);
+        }
+        if !self.seekable {
+            return Err(io::Error::new(io::ErrorKind::Unsupported, "Stream is not seekable"));
+        }
+        self.request_from(target)?;
+        Ok(target)
+    }
+}
+
+impl MediaSource for HttpStream {
+    fn is_seekable(&self) -> bool {
+        self.seekable
+    }
+
+    fn byte_len(&self) -> Option<u64> {
+        self.length
+    }
+}
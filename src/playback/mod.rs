@@ -1,7 +1,19 @@
 pub const SAMPLE_RATE: f32 = 48000.0;
 
+pub mod clock;
+pub mod decoder;
 pub mod filters;
 pub mod filters_manager;
+pub mod frame_counter;
+pub mod hls_stream;
+pub mod http_stream;
+pub mod mp3_index;
+pub mod opus;
+pub mod pacer;
 pub mod player;
+pub mod resampler;
 pub mod stream_processor;
+#[cfg(test)]
+pub mod test_utils;
+pub mod volume;
 pub mod audio_engine;
\ No newline at end of file
@@ -0,0 +1,225 @@
+use std::io::{Read, Seek, SeekFrom};
+
+// Enough of the first frame for the Xing/Info or VBRI header and a typical VBRI table
+const FIRST_FRAME_BYTES: usize = 4096;
+// Covers the largest possible frame twice over, so a sync point can be confirmed by the next one
+const SYNC_BYTES: usize = 8192;
+
+/// Seek table of a raw MP3 stream, from the Xing/Info TOC or VBRI table in its first frame.
+/// Maps playback time to byte offsets so a seek can jump straight to the right place.
+pub struct Mp3Index {
+    // (fraction of the duration, absolute byte offset), ascending
+    points: Vec<(f64, u64)>,
+    duration: f64,
+    frame_duration: f64,
+}
+
+impl Mp3Index {
+    /// Reads the index from the start of the stream. `None` for streams without one,
+    /// such as CBR files without an Info tag, or MP3 inside another container.
+    pub fn read<R: Read + Seek>(source: &mut R) -> Option<Self> {
+        source.seek(SeekFrom::Start(0)).ok()?;
+        let mut first_frame = skip_id3(source)?;
+
+        let mut frame = vec![0u8; FIRST_FRAME_BYTES];
+        let mut filled = 0;
+        while filled < frame.len() {
+            match source.read(&mut frame[filled..]) {
+                Ok(0) => break,
+                Ok(read) => filled += read,
+                Err(_) => return None,
+            }
+        }
+        frame.truncate(filled);
+
+        let header = FrameHeader::parse(&frame)?;
+        let xing = 4 + header.side_info_len();
+        if frame.get(xing..xing + 4).is_some_and(|tag| tag == b"Xing" || tag == b"Info") {
+            Self::from_xing(&frame[xing..], &header, first_frame)
+        } else if frame.get(36..40).is_some_and(|tag| tag == b"VBRI") {
+            // The VBRI table counts from the end of its own frame
+            first_frame += header.frame_len() as u64;
+            Self::from_vbri(&frame[36..], &header, first_frame)
+        } else {
+            None
+        }
+    }
+
+    /// Byte offset where playback at `seconds` roughly starts. It may fall inside a frame.
+    pub fn locate(&self, seconds: f64) -> u64 {
+        let fraction = (seconds / self.duration).clamp(0.0, 1.0);
+        let next = self.points.partition_point(|(f, _)| *f <= fraction).clamp(1, self.points.len() - 1);
+        let (f0, b0) = self.points[next - 1];
+        let (f1, b1) = self.points[next];
+        let t = if f1 > f0 { (fraction - f0) / (f1 - f0) } else { 0.0 };
+        b0 + ((b1.saturating_sub(b0)) as f64 * t) as u64
+    }
+
+    /// Playback time of the frame starting at byte `offset`, the inverse of `locate`.
+    pub fn time_at(&self, offset: u64) -> f64 {
+        let next = self.points.partition_point(|(_, b)| *b <= offset).clamp(1, self.points.len() - 1);
+        let (f0, b0) = self.points[next - 1];
+        let (f1, b1) = self.points[next];
+        let t = if b1 > b0 { (offset.saturating_sub(b0) as f64 / (b1 - b0) as f64).min(1.0) } else { 0.0 };
+        let seconds = (f0 + (f1 - f0) * t) * self.duration;
+        // Frames start on whole multiples of their duration, which evens out the table's rounding
+        (seconds / self.frame_duration).round() * self.frame_duration
+    }
+
+    fn from_xing(tag: &[u8], header: &FrameHeader, first_frame: u64) -> Option<Self> {
+        let flags = read_u32(tag, 4)?;
+        // Frames, bytes and TOC all have to be present
+        if flags & 0x7 != 0x7 {
+            return None;
+        }
+        let frames = read_u32(tag, 8)? as u64;
+        let bytes = read_u32(tag, 12)? as u64;
+        let toc = tag.get(16..116)?;
+
+        let mut points: Vec<(f64, u64)> = toc
+            .iter()
+            .enumerate()
+            .map(|(percent, &entry)| (percent as f64 / 100.0, first_frame + entry as u64 * bytes / 256))
+            .collect();
+        points.push((1.0, first_frame + bytes));
+        Self::new(points, frames, header)
+    }
+
+    fn from_vbri(tag: &[u8], header: &FrameHeader, first_frame: u64) -> Option<Self> {
+        let frames = read_u32(tag, 14)? as u64;
+        let entries = read_u16(tag, 18)? as usize;
+        let scale = read_u16(tag, 20)? as u64;
+        let entry_size = read_u16(tag, 22)? as usize;
+        let frames_per_entry = read_u16(tag, 24)? as u64;
+        if frames == 0 || entries == 0 || !(1..=4).contains(&entry_size) {
+            return None;
+        }
+
+        let mut points = vec![(0.0, first_frame)];
+        let mut offset = first_frame;
+        for entry in 0..entries {
+            let start = 26 + entry * entry_size;
+            let size = tag
+                .get(start..start + entry_size)?
+                .iter()
+                .fold(0u64, |size, &byte| size << 8 | byte as u64);
+            offset += size * scale;
+            let fraction = ((entry as u64 + 1) * frames_per_entry) as f64 / frames as f64;
+            points.push((fraction.min(1.0), offset));
+        }
+        Self::new(points, frames, header)
+    }
+
+    fn new(points: Vec<(f64, u64)>, frames: u64, header: &FrameHeader) -> Option<Self> {
+        let frame_duration = header.samples_per_frame() as f64 / header.sample_rate as f64;
+        let duration = frames as f64 * frame_duration;
+        (duration > 0.0 && points.len() >= 2).then_some(Self { points, duration, frame_duration })
+    }
+}
+
+/// Moves `source` to the first frame at or after `offset` and returns where that is. A header
+/// only counts if another one follows it, so stray sync bits in audio data are passed over.
+pub fn sync<R: Read + Seek>(source: &mut R, offset: u64) -> Option<u64> {
+    source.seek(SeekFrom::Start(offset)).ok()?;
+    let mut data = Vec::with_capacity(SYNC_BYTES);
+    source.by_ref().take(SYNC_BYTES as u64).read_to_end(&mut data).ok()?;
+
+    let found = (0..data.len()).find(|&at| {
+        FrameHeader::parse(&data[at..]).is_some_and(|header| {
+            let next = at + header.frame_len();
+            if header.bitrate == 0 {
+                false
+            } else if next + 4 > data.len() {
+                // The last frame of the stream has nothing after it
+                data.len() < SYNC_BYTES
+            } else {
+                FrameHeader::parse(&data[next..]).is_some()
+            }
+        })
+    })?;
+    let frame = offset + found as u64;
+    source.seek(SeekFrom::Start(frame)).ok()?;
+    Some(frame)
+}
+
+struct FrameHeader {
+    mpeg1: bool,
+    mono: bool,
+    sample_rate: u32,
+    bitrate: u32,
+    padding: bool,
+}
+
+impl FrameHeader {
+    /// Parses a layer III frame header.
+    fn parse(frame: &[u8]) -> Option<Self> {
+        let header = read_u32(frame, 0)?;
+        let version = (header >> 19) & 0x3;
+        let layer = (header >> 17) & 0x3;
+        if header >> 21 != 0x7FF || version == 1 || layer != 1 {
+            return None;
+        }
+        let mpeg1 = version == 3;
+        let rates = [44100, 48000, 32000];
+        let sample_rate = *rates.get(((header >> 10) & 0x3) as usize)? >> match version {
+            3 => 0,
+            2 => 1,
+            _ => 2,
+        };
+        let bitrates: [u32; 15] = if mpeg1 {
+            [0, 32, 40, 48, 56, 64, 80, 96, 112, 128, 160, 192, 224, 256, 320]
+        } else {
+            [0, 8, 16, 24, 32, 40, 48, 56, 64, 80, 96, 112, 128, 144, 160]
+        };
+        Some(Self {
+            mpeg1,
+            mono: (header >> 6) & 0x3 == 3,
+            sample_rate,
+            bitrate: *bitrates.get(((header >> 12) & 0xF) as usize)? * 1000,
+            padding: (header >> 9) & 0x1 == 1,
+        })
+    }
+
+    fn side_info_len(&self) -> usize {
+        match (self.mpeg1, self.mono) {
+            (true, false) => 32,
+            (true, true) | (false, false) => 17,
+            (false, true) => 9,
+        }
+    }
+
+    fn samples_per_frame(&self) -> u64 {
+        if self.mpeg1 { 1152 } else { 576 }
+    }
+
+    fn frame_len(&self) -> usize {
+        let per_slot = self.samples_per_frame() as u32 / 8;
+        (per_slot * self.bitrate / self.sample_rate) as usize + self.padding as usize
+    }
+}
+
+/// Skips any ID3v2 tags and returns the offset of what follows.
+fn skip_id3<R: Read + Seek>(source: &mut R) -> Option<u64> {
+    let mut offset = 0;
+    loop {
+        let mut header = [0u8; 10];
+        source.read_exact(&mut header).ok()?;
+        if &header[..3] != b"ID3" {
+            source.seek(SeekFrom::Start(offset)).ok()?;
+            return Some(offset);
+        }
+        // Sizes are syncsafe, 7 bits per byte
+        let size = header[6..10].iter().fold(0u64, |size, &byte| size << 7 | (byte & 0x7F) as u64);
+        let footer = if header[5] & 0x10 != 0 { 10 } else { 0 };
+        offset += 10 + size + footer;
+        source.seek(SeekFrom::Start(offset)).ok()?;
+    }
+}
+
+fn read_u32(data: &[u8], at: usize) -> Option<u32> {
+    Some(u32::from_be_bytes(data.get(at..at + 4)?.try_into().ok()?))
+}
+
+fn read_u16(data: &[u8], at: usize) -> Option<u16> {
+    Some(u16::from_be_bytes(data.get(at..at + 2)?.try_into().ok()?))
+}
@@ -0,0 +1,156 @@
+use unsafe_libopus::{
+    opus_decode, opus_decoder_create, opus_decoder_destroy, opus_encode, opus_encoder_create,
+    opus_encoder_ctl, opus_encoder_destroy, OpusDecoder as RawDecoder, OpusEncoder as RawEncoder,
+    OPUS_APPLICATION_AUDIO, OPUS_SET_BITRATE_REQUEST, OPUS_SET_COMPLEXITY_REQUEST,
+    OPUS_SET_INBAND_FEC_REQUEST, OPUS_SET_PACKET_LOSS_PERC_REQUEST,
+};
+
+use crate::types::config::OpusConfig;
+use super::SAMPLE_RATE;
+
+pub const CHANNELS: usize = 2;
+/// Samples per channel in a 20ms frame, the only frame size Discord accepts.
+pub const FRAME_SIZE: usize = SAMPLE_RATE as usize / 1000 * 20;
+// Generous upper bound, a single Opus frame never exceeds 1275 bytes
+const MAX_PACKET_BYTES: usize = 4000;
+/// Longest Opus packet is 120ms.
+const MAX_FRAME_SAMPLES: usize = SAMPLE_RATE as usize / 1000 * 120;
+
+/// Decodes Opus packets (WebM/Ogg sources) to 48kHz stereo interleaved PCM.
+pub struct OpusDecoder {
+    decoder: *mut RawDecoder,
+    output: Vec<i16>,
+}
+
+// The raw decoder state is only ever touched through `&mut self`.
+unsafe impl Send for OpusDecoder {}
+
+impl OpusDecoder {
+    pub fn new() -> Result<Self, String> {
+        let mut error = 0;
+        let decoder = unsafe { opus_decoder_create(SAMPLE_RATE as i32, CHANNELS as i32, &mut error) };
+        if decoder.is_null() || error != 0 {
+            return Err(format!("Failed to create Opus decoder ({})", error));
+        }
+        Ok(Self {
+            decoder,
+            output: vec![0; MAX_FRAME_SAMPLES * CHANNELS],
+        })
+    }
+
+    pub fn decode(&mut self, packet: &[u8]) -> Result<&[i16], String> {
+        let samples = unsafe {
+            opus_decode(
+                self.decoder,
+                packet.as_ptr(),
+                packet.len() as i32,
+                self.output.as_mut_ptr(),
+                MAX_FRAME_SAMPLES as i32,
+                0,
+            )
+        };
+        if samples < 0 {
+            return Err(format!("Failed to decode Opus packet ({})", samples));
+        }
+        Ok(&self.output[..samples as usize * CHANNELS])
+    }
+}
+
+impl Drop for OpusDecoder {
+    fn drop(&mut self) {
+        unsafe { opus_decoder_destroy(self.decoder) };
+    }
+}
+
+/// Encodes 20ms frames of 48kHz stereo interleaved PCM into Opus packets. Independent of
+/// where the packets go, so voice connections and file or HTTP sinks can all share it.
+pub struct OpusEncoder {
+    encoder: *mut RawEncoder,
+    output: Vec<u8>,
+}
+
+// The raw encoder state is only ever touched through `&mut self`.
+unsafe impl Send for OpusEncoder {}
+
+impl OpusEncoder {
+    pub fn new(config: &OpusConfig) -> Result<Self, String> {
+        let mut error = 0;
+        let encoder = unsafe {
+            opus_encoder_create(SAMPLE_RATE as i32, CHANNELS as i32, OPUS_APPLICATION_AUDIO, &mut error)
+        };
+        if encoder.is_null() || error != 0 {
+            return Err(format!("Failed to create Opus encoder ({})", error));
+        }
+        let mut encoder = Self {
+            encoder,
+            output: vec![0; MAX_PACKET_BYTES],
+        };
+        encoder.configure(config)?;
+        Ok(encoder)
+    }
+
+    /// Applies new settings; they take effect from the next frame.
+    pub fn configure(&mut self, config: &OpusConfig) -> Result<(), String> {
+        let settings = [
+            (OPUS_SET_BITRATE_REQUEST, config.bitrate as i32, "bitrate"),
+            (OPUS_SET_COMPLEXITY_REQUEST, config.complexity as i32, "complexity"),
+            (OPUS_SET_INBAND_FEC_REQUEST, config.fec as i32, "FEC"),
+            (OPUS_SET_PACKET_LOSS_PERC_REQUEST, config.packet_loss_percent as i32, "packet loss"),
+        ];
+        for (request, value, name) in settings {
+            let result = unsafe { opus_encoder_ctl!(self.encoder, request, value) };
+            if result != 0 {
+                return Err(format!("Failed to set Opus {} to {} ({})", name, value, result));
+            }
+        }
+        Ok(())
+    }
+
+    /// Encodes exactly one frame (`FRAME_SIZE * CHANNELS` samples).
+    pub fn encode(&mut self, pcm: &[i16]) -> Result<&[u8], String> {
+        if pcm.len() != FRAME_SIZE * CHANNELS {
+            return Err(format!(
+                "Opus frames must be {} samples, got {}",
+                FRAME_SIZE * CHANNELS,
+                pcm.len()
+            ));
+        }
+        let bytes = unsafe {
+            opus_encode(
+                self.encoder,
+                pcm.as_ptr(),
+                FRAME_SIZE as i32,
+                self.output.as_mut_ptr(),
+                self.output.len() as i32,
+            )
+        };
+        if bytes < 0 {
+            return Err(format!("Failed to encode Opus frame ({})", bytes));
+        }
+        Ok(&self.output[..bytes as usize])
+    }
+}
+
+impl Drop for OpusEncoder {
+    fn drop(&mut self) {
+        unsafe { opus_encoder_destroy(self.encoder) };
+    }
+}
+
+/// Samples per channel in an Opus packet, read from its TOC byte (RFC 6716 section 3.1).
+pub fn packet_samples(packet: &[u8]) -> Option<usize> {
+    let toc = *packet.first()?;
+    let config = (toc >> 3) as usize;
+    // Frame durations at 48kHz: SILK 10-60ms, hybrid 10-20ms, CELT 2.5-20ms
+    let frame_samples = match config {
+        0..=11 => [480, 960, 1920, 2880][config % 4],
+        12..=15 => [480, 960][config % 2],
+        _ => [120, 240, 480, 960][config % 4],
+    };
+    let frames = match toc & 0x3 {
+        0 => 1,
+        1 | 2 => 2,
+        _ => (*packet.get(1)? & 0x3F) as usize,
+    };
+    Some(frame_samples * frames)
+}
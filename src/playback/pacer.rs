@@ -0,0 +1,130 @@
+use std::sync::Arc;
+use std::time::Duration;
+
+use super::clock::Clock;
+
+pub const FRAME_DURATION: Duration = Duration::from_millis(20);
+/// Late frames sent back to back to catch up; anything further behind is dropped.
+const MAX_CATCH_UP_FRAMES: u64 = 5;
+
+/// What to do at a grid point: send `due` frames now and count `dropped` as deficit.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct PaceTick {
+    pub due: u64,
+    pub dropped: u64,
+}
+
+/// Schedules frames on an absolute 20ms grid, so time spent between ticks doesn't add up
+/// to drift the way a relative sleep would.
+pub struct FramePacer {
+    clock: Arc<dyn Clock>,
+    // Index of the next grid point, counted from the clock's start
+    next_frame: u64,
+}
+
+impl FramePacer {
+    pub fn new(clock: Arc<dyn Clock>) -> Self {
+        let next_frame = (clock.now().as_nanos() / FRAME_DURATION.as_nanos()) as u64 + 1;
+        Self { clock, next_frame }
+    }
+
+    /// Waits for the next grid point. Cancel safe: the grid only moves once the wait is over.
+    pub async fn next(&mut self) -> PaceTick {
+        let deadline = Duration::from_nanos(FRAME_DURATION.as_nanos() as u64 * self.next_frame);
+        self.clock.sleep_until(deadline).await;
+
+        let late = self.clock.now().saturating_sub(deadline);
+        // Grid points that have passed, including the one we waited for
+        let passed = (late.as_nanos() / FRAME_DURATION.as_nanos()) as u64 + 1;
+        self.next_frame += passed;
+
+        let due = passed.min(MAX_CATCH_UP_FRAMES);
+        PaceTick { due, dropped: passed - due }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::playback::clock::VirtualClock;
+    use crate::playback::frame_counter::{FrameCounter, EXPECTED_FRAMES_PER_MINUTE};
+
+    fn pacer() -> (Arc<VirtualClock>, FramePacer) {
+        let clock = Arc::new(VirtualClock::new());
+        let pacer = FramePacer::new(clock.clone());
+        (clock, pacer)
+    }
+
+    fn ms(millis: u64) -> Duration {
+        Duration::from_millis(millis)
+    }
+
+    #[tokio::test]
+    async fn ticks_on_the_grid() {
+        let (clock, mut pacer) = pacer();
+        for frame in 1..=50 {
+            assert_eq!(pacer.next().await, PaceTick { due: 1, dropped: 0 });
+            assert_eq!(clock.now(), FRAME_DURATION * frame);
+        }
+    }
+
+    #[tokio::test]
+    async fn stays_on_the_grid_after_time_lost_between_ticks() {
+        let (clock, mut pacer) = pacer();
+        pacer.next().await;
+        // Less than a frame late doesn't move the following grid points
+        clock.advance(ms(15));
+        assert_eq!(pacer.next().await, PaceTick { due: 1, dropped: 0 });
+        assert_eq!(clock.now(), ms(40));
+        assert_eq!(pacer.next().await, PaceTick { due: 1, dropped: 0 });
+        assert_eq!(clock.now(), ms(60));
+    }
+
+    #[tokio::test]
+    async fn catches_up_after_falling_behind() {
+        let (clock, mut pacer) = pacer();
+        pacer.next().await;
+        // The grid points at 40ms and 60ms have passed by 70ms
+        clock.advance(ms(50));
+        assert_eq!(pacer.next().await, PaceTick { due: 2, dropped: 0 });
+        assert_eq!(pacer.next().await, PaceTick { due: 1, dropped: 0 });
+        assert_eq!(clock.now(), ms(80));
+    }
+
+    #[tokio::test]
+    async fn drops_frames_beyond_the_catch_up_limit() {
+        let (clock, mut pacer) = pacer();
+        pacer.next().await;
+        // A 200ms stall passes 10 grid points, 20ms to 220ms exclusive of the first
+        clock.advance(ms(200));
+        assert_eq!(pacer.next().await, PaceTick { due: MAX_CATCH_UP_FRAMES, dropped: 10 - MAX_CATCH_UP_FRAMES });
+        assert_eq!(pacer.next().await, PaceTick { due: 1, dropped: 0 });
+        assert_eq!(clock.now(), ms(240));
+    }
+
+    #[tokio::test]
+    async fn records_deficits_in_frame_stats() {
+        let (clock, mut pacer) = pacer();
+        let mut counter = FrameCounter::new();
+        let mut stalled = false;
+
+        // Like the engine: each due frame is sent, dropped ones count as deficit
+        while counter.last_minute().is_none() {
+            if !stalled && clock.now() >= ms(30_000) {
+                stalled = true;
+                clock.advance(ms(300));
+            }
+            let tick = pacer.next().await;
+            for _ in 0..tick.due {
+                counter.sent();
+            }
+            counter.missed(tick.dropped);
+        }
+
+        let stats = counter.last_minute().unwrap();
+        assert_eq!(stats.expected, EXPECTED_FRAMES_PER_MINUTE);
+        assert_eq!(stats.deficit, 15 - MAX_CATCH_UP_FRAMES);
+        assert_eq!(stats.sent + stats.nulled + stats.deficit, EXPECTED_FRAMES_PER_MINUTE);
+        assert_eq!(clock.now(), FRAME_DURATION * EXPECTED_FRAMES_PER_MINUTE as u32);
+    }
+}
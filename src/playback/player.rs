@@ -1,87 +1,370 @@
+use std::future::Future;
 use std::time::SystemTime;
 use serde_json::{json, Value};
-use crate::types::stats::RustlinkMock;
+use tokio::sync::mpsc;
+use crate::types::audio_engine::PlayerEvent;
+use crate::types::config::AudioConfig;
+use crate::types::stats::{FrameStats, RustlinkMock};
+use crate::utils::logger;
+use crate::voice::connection::VoiceConnection;
+use crate::voice::gateway::VoiceServerInfo;
 // I don't have access to RustlinkMock definition easily, but I can use generic placeholders.
 
+use super::decoder::StreamInput;
 use super::filters_manager::FiltersManager;
-use super::stream_processor::{create_audio_resource, AudioResource};
+use super::frame_counter::FrameCounter;
+use super::opus::{OpusEncoder, FRAME_SIZE};
+use super::stream_processor::{create_audio_resource, AudioResource, FrameResult};
+use super::SAMPLE_RATE;
+
+/// Gap without frames after which a playing track is reported as stuck.
+const TRACK_STUCK_THRESHOLD_MS: u64 = 10_000;
+const FRAME_DURATION_MS: i64 = 20;
 
 pub struct Player {
+    pub session_id: String,
     pub guild_id: String,
+    pub user_id: String,
     pub track: Option<Value>, // Using Value for track info
     pub is_paused: bool,
     pub volume_percent: u32,
     pub filters: Value, // Store current filters config
     pub position: i64,
+    pub end_time: Option<i64>,
     pub conn_status: String,
-    // voice: VoiceState, 
-    // connection: Option<VoiceConnection>,
-    
+    pub voice: Option<VoiceConnection>,
+
     // Internal state
     filters_manager: Option<FiltersManager>,
     audio_resource: Option<AudioResource>,
+    events: mpsc::UnboundedSender<PlayerEvent>,
+    // Consecutive frame slots with nothing decoded
+    starved_frames: u64,
+    frame_counter: Option<FrameCounter>,
+    stuck_reported: bool,
+    audio_config: AudioConfig,
+    // Position of the last start or seek, and the track samples sent since then
+    position_base: i64,
+    sent_samples: f64,
+    // Both as they were before a seek still in progress, restored if it fails
+    seek_from: Option<(i64, f64)>,
+    // Created with the first track and kept across tracks
+    encoder: Option<OpusEncoder>,
 }
 
 impl Player {
-    pub fn new(guild_id: String) -> Self {
+    pub fn new(
+        session_id: String,
+        guild_id: String,
+        user_id: String,
+        events: mpsc::UnboundedSender<PlayerEvent>,
+        audio_config: AudioConfig,
+    ) -> Self {
         Self {
+            session_id,
             guild_id,
+            user_id,
             track: None,
             is_paused: false,
             volume_percent: 100,
             filters: json!({}),
             position: 0,
+            end_time: None,
             conn_status: "idle".to_string(),
+            voice: None,
             filters_manager: None,
             audio_resource: None,
+            events,
+            starved_frames: 0,
+            frame_counter: None,
+            stuck_reported: false,
+            audio_config,
+            position_base: 0,
+            sent_samples: 0.0,
+            seek_from: None,
+            encoder: None,
         }
     }
 
-    pub fn play(&mut self, encoded: String, info: Value, no_replace: bool) -> bool {
+    pub fn play(
+        &mut self,
+        encoded: String,
+        info: Value,
+        user_data: Value,
+        no_replace: bool,
+        stream: impl Future<Output = Result<StreamInput, String>> + Send + 'static,
+    ) -> bool {
         if no_replace && self.track.is_some() {
             return false;
         }
+        self.end_track("replaced");
 
         self.track = Some(json!({
             "encoded": encoded,
-            "info": info
+            "info": info,
+            "pluginInfo": {},
+            "userData": user_data
         }));
+        self.set_position(0);
+        self.seek_from = None;
+        self.end_time = None;
+
+        if self.encoder.is_none() {
+            match OpusEncoder::new(&self.audio_config.opus) {
+                Ok(encoder) => self.encoder = Some(encoder),
+                Err(e) => {
+                    self.fail(&e, "fault", "Failed to create Opus encoder");
+                    return false;
+                }
+            }
+        }
 
-        // In Node: resolves URL, connects, plays.
-        // Here we stub.
-        
         let initial_filters = &self.filters;
-        self.audio_resource = Some(create_audio_resource(&info, initial_filters));
+        let resource = create_audio_resource(
+            &info,
+            initial_filters,
+            self.volume_percent,
+            self.audio_config.resampling_quality,
+            stream,
+        );
+        match resource {
+            Ok(resource) => self.audio_resource = Some(resource),
+            Err(e) => {
+                self.fail(&e, "common", "Failed to create audio resource");
+                return false;
+            }
+        }
+        self.update_passthrough();
         
-        // Mock playing
         self.conn_status = "playing".to_string();
         self.is_paused = false;
+        self.starved_frames = 0;
+        self.stuck_reported = false;
+        self.frame_counter = Some(FrameCounter::new());
+
+        let track = self.track.clone();
+        self.emit("TrackStartEvent", json!({ "track": track }));
         
         true
     }
 
     pub fn stop(&mut self) -> bool {
-        if self.track.is_none() {
-            return false;
+        self.end_track("stopped")
+    }
+
+    /// Pulls the next 20ms frame, ending the track when the stream runs out or reaches `endTime`.
+    /// Called by the engine once per frame slot.
+    pub fn tick(&mut self) {
+        while let Some(closed) = self.voice.as_mut().and_then(|voice| voice.poll_closed()) {
+            // No close frame means the socket dropped, reported as an abnormal closure
+            self.voice_closed(closed.code.unwrap_or(1006), &closed.reason, closed.by_remote);
+        }
+
+        if self.track.is_none() || self.is_paused {
+            return;
+        }
+        let Some(resource) = &mut self.audio_resource else {
+            return;
+        };
+        let track_speed = resource.filters.track_speed() as f64;
+
+        // Nothing is read until the client sends its voice state, so the track starts there
+        let Some(voice) = &self.voice else {
+            if let Some(counter) = &mut self.frame_counter {
+                counter.nulled();
+            }
+            return;
+        };
+        // Hold the buffer while the voice connection is (re)established so playback
+        // continues where it left off, e.g. after a region move
+        if !voice.is_connected() {
+            let closed = voice.is_closed();
+            if let Some(counter) = &mut self.frame_counter {
+                counter.nulled();
+            }
+            // A connection that gave up only comes back with a new voice state from the client,
+            // which gets until the stuck threshold before the track is dropped
+            if closed && self.starve() {
+                self.end_track("cleanup");
+            }
+            return;
+        }
+
+        match resource.read_frame() {
+            FrameResult::Frame(pcm) => {
+                let Some(encoder) = &mut self.encoder else {
+                    return;
+                };
+                match encoder.encode(&pcm) {
+                    Ok(packet) => {
+                        if let Some(voice) = &self.voice {
+                            voice.send_frame(packet);
+                        }
+                    }
+                    Err(e) => {
+                        self.fail(&e, "fault", "Failed to encode the track");
+                        return;
+                    }
+                }
+            }
+            FrameResult::Packet(packet) => {
+                if let Some(voice) = &self.voice {
+                    voice.send_frame(&packet);
+                }
+            }
+            FrameResult::Buffering => {
+                if let Some(counter) = &mut self.frame_counter {
+                    counter.nulled();
+                }
+                self.starve();
+                return;
+            }
+            FrameResult::SeekFailed { error, skipped } => {
+                logger("warn", "Player", &format!("{} in guild {}", error, self.guild_id), None);
+                if let Some((position_base, sent_samples)) = self.seek_from.take() {
+                    self.position_base = position_base;
+                    self.sent_samples = sent_samples + skipped;
+                    self.update_position();
+                }
+                return;
+            }
+            FrameResult::Finished => {
+                self.end_track("finished");
+                return;
+            }
+            FrameResult::Error(e) => {
+                self.fail(&e, "fault", "Failed to decode the track stream");
+                return;
+            }
+        }
+
+        // A timescale speeding the track up or slowing it down changes how much of it a frame covers
+        self.sent_samples += FRAME_SIZE as f64 * track_speed;
+        self.update_position();
+        self.seek_from = None;
+        self.starved_frames = 0;
+        self.stuck_reported = false;
+        if let Some(counter) = &mut self.frame_counter {
+            counter.sent();
+        }
+
+        if self.end_time.is_some_and(|end| self.position >= end) {
+            self.end_track("finished");
+        }
+    }
+
+    /// Counts a frame slot without audio sent, reporting the track as stuck once they add up
+    /// to the threshold. Returns whether it is stuck.
+    fn starve(&mut self) -> bool {
+        self.starved_frames += 1;
+        let stalled = self.starved_frames * FRAME_DURATION_MS as u64 >= TRACK_STUCK_THRESHOLD_MS;
+        if stalled && !self.stuck_reported {
+            self.stuck_reported = true;
+            self.emit("TrackStuckEvent", json!({
+                "track": self.track,
+                "thresholdMs": TRACK_STUCK_THRESHOLD_MS
+            }));
         }
-        self.track = None;
+        stalled
+    }
+
+    /// Reports a playback failure, ending the track with `loadFailed`.
+    pub fn fail(&mut self, message: &str, severity: &str, cause: &str) {
+        self.emit("TrackExceptionEvent", json!({
+            "track": self.track,
+            "exception": {
+                "message": message,
+                "severity": severity,
+                "cause": cause
+            }
+        }));
+        self.end_track("loadFailed");
+    }
+
+    /// Reports that the voice gateway connection was closed.
+    pub fn voice_closed(&mut self, code: u16, reason: &str, by_remote: bool) {
+        self.conn_status = "idle".to_string();
+        self.emit("WebSocketClosedEvent", json!({
+            "code": code,
+            "reason": reason,
+            "byRemote": by_remote
+        }));
+    }
+
+    pub fn is_playing(&self) -> bool {
+        self.track.is_some() && !self.is_paused
+    }
+
+    /// Records frame slots the engine skipped because it fell behind.
+    pub fn frames_missed(&mut self, frames: u64) {
+        if self.is_playing()
+            && let Some(counter) = &mut self.frame_counter
+        {
+            counter.missed(frames);
+        }
+    }
+
+    /// Frame stats of the last full minute of playback, if the player has been playing that long.
+    pub fn frame_stats(&self) -> Option<FrameStats> {
+        self.frame_counter.as_ref().and_then(|counter| counter.last_minute())
+    }
+
+    fn update_position(&mut self) {
+        self.position = self.position_base + (self.sent_samples * 1000.0 / SAMPLE_RATE as f64) as i64;
+    }
+
+    fn set_position(&mut self, position: i64) {
+        self.position = position;
+        self.position_base = position;
+        self.sent_samples = 0.0;
+    }
+
+    fn end_track(&mut self, reason: &str) -> bool {
+        let Some(track) = self.track.take() else {
+            return false;
+        };
         self.audio_resource = None;
+        if let Some(voice) = &self.voice {
+            voice.stop();
+        }
+        self.frame_counter = None;
         self.conn_status = "idle".to_string();
+        self.emit("TrackEndEvent", json!({ "track": track, "reason": reason }));
         true
     }
 
+    fn emit(&self, event_type: &str, data: Value) {
+        let mut payload = json!({
+            "op": "event",
+            "type": event_type,
+            "guildId": self.guild_id
+        });
+        if let (Some(payload), Value::Object(data)) = (payload.as_object_mut(), data) {
+            payload.extend(data);
+        }
+        let _ = self.events.send(PlayerEvent {
+            session_id: self.session_id.clone(),
+            payload,
+        });
+    }
+
     pub fn pause(&mut self, should_pause: bool) -> bool {
         if self.is_paused == should_pause {
             return false;
         }
         self.is_paused = should_pause;
-        // Mock pause logic
+        if should_pause && let Some(voice) = &self.voice {
+            voice.stop();
+        }
         true
     }
 
     pub fn set_volume(&mut self, level: u32) -> bool {
         self.volume_percent = level.clamp(0, 1000);
-        // Apply to resource if exists
+        if let Some(resource) = &mut self.audio_resource {
+            resource.volume.set(self.volume_percent);
+        }
+        self.update_passthrough();
         true
     }
 
@@ -91,29 +374,99 @@ impl Player {
         if let Some(resource) = &mut self.audio_resource {
             resource.filters.update(&self.filters);
         }
+        self.update_passthrough();
         true
     }
 
-    pub fn seek(&mut self, position: i64) -> bool {
+    /// Opus sources skip decoding and re-encoding while there is nothing to apply.
+    fn update_passthrough(&mut self) {
+        if let Some(resource) = &mut self.audio_resource {
+            let enabled = self.volume_percent == 100 && !resource.filters.is_active();
+            resource.set_passthrough(enabled);
+        }
+    }
+
+    /// Applies reloaded audio settings. Opus settings take effect from the next frame,
+    /// the resampling quality from the next track.
+    pub fn set_audio_config(&mut self, config: &AudioConfig) {
+        self.audio_config = config.clone();
+        if let Some(encoder) = &mut self.encoder
+            && let Err(e) = encoder.configure(&config.opus)
+        {
+            logger("warn", "Player", &format!("Failed to apply Opus settings: {}", e), None);
+        }
+    }
+
+    pub fn set_end_time(&mut self, end_time: Option<i64>) -> bool {
         if self.track.is_none() {
             return false;
         }
-        self.position = position;
-        // Mock seek logic
+        self.end_time = end_time;
+        true
+    }
+
+    pub fn set_user_data(&mut self, user_data: Value) -> bool {
+        match &mut self.track {
+            Some(track) => {
+                track["userData"] = user_data;
+                true
+            }
+            None => false,
+        }
+    }
+
+    pub fn seek(&mut self, position: i64) -> bool {
+        let Some(track) = &self.track else {
+            return false;
+        };
+        // Live streams can't be seeked
+        if track["info"]["isSeekable"].as_bool() == Some(false) {
+            return false;
+        }
+        let position = position.max(0);
+        if let Some(resource) = &mut self.audio_resource {
+            resource.seek(position as u64);
+            self.seek_from.get_or_insert((self.position_base, self.sent_samples));
+        }
+        self.set_position(position);
+        self.starved_frames = 0;
         true
     }
     
-    pub fn update_voice(&mut self, _session_id: &str, _token: &str, _endpoint: &str) {
-        // Mock connection logic
-        self.conn_status = "connected".to_string();
+    /// Connects to the voice server, replacing the current connection unless the state is unchanged.
+    pub fn update_voice(&mut self, session_id: &str, token: &str, endpoint: &str) {
+        if session_id.is_empty() || token.is_empty() || endpoint.is_empty() {
+            return;
+        }
+        let info = VoiceServerInfo {
+            guild_id: self.guild_id.clone(),
+            user_id: self.user_id.clone(),
+            session_id: session_id.to_string(),
+            token: token.to_string(),
+            endpoint: endpoint.to_string(),
+        };
+        // The same state reconnects a connection that gave up, e.g. after being disconnected
+        if self.voice.as_ref().is_some_and(|voice| voice.info == info && !voice.is_closed()) {
+            return;
+        }
+        self.voice = Some(VoiceConnection::connect(info));
     }
     
     pub fn destroy(&mut self) {
-        self.stop();
+        self.end_track("cleanup");
+        self.voice = None;
         self.conn_status = "destroyed".to_string();
     }
 
     pub fn to_json(&self) -> Value {
+        let voice = match &self.voice {
+            Some(voice) => json!({
+                "token": voice.info.token,
+                "endpoint": voice.info.endpoint,
+                "sessionId": voice.info.session_id
+            }),
+            None => json!({}),
+        };
         json!({
             "guildId": self.guild_id,
             "track": self.track,
@@ -123,10 +476,36 @@ impl Player {
             "state": {
                 "time": SystemTime::now().duration_since(SystemTime::UNIX_EPOCH).unwrap_or_default().as_millis() as u64,
                 "position": self.position,
-                "connected": self.conn_status == "connected",
-                "ping": 0
+                "connected": self.voice.as_ref().is_some_and(|voice| voice.is_connected()),
+                "ping": self.voice.as_ref().map_or(-1, |voice| voice.ping())
             },
-            "voice": {} 
+            "voice": voice
         })
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::path::Path;
+    use crate::playback::frame_counter::EXPECTED_FRAMES_PER_MINUTE;
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn waits_for_a_voice_connection_before_playing() {
+        let (events, _events_rx) = mpsc::unbounded_channel();
+        let mut player = Player::new("s".to_string(), "1".to_string(), "2".to_string(), events, AudioConfig::default());
+        let path = Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/sine.wav");
+        let info = json!({ "sourceName": "local", "uri": path.to_str() });
+        assert!(player.play(String::new(), info, Value::Null, false, async { Ok(StreamInput::File(path)) }));
+        // Let the decoder fill the buffer, so frames are there to be sent
+        tokio::time::sleep(std::time::Duration::from_millis(200)).await;
+
+        for _ in 0..EXPECTED_FRAMES_PER_MINUTE {
+            player.tick();
+        }
+        assert!(player.is_playing());
+        assert_eq!(player.position, 0);
+        let stats = player.frame_stats().unwrap();
+        assert_eq!((stats.sent, stats.nulled), (0, EXPECTED_FRAMES_PER_MINUTE));
+    }
+}
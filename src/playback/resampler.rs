@@ -0,0 +1,218 @@
+use std::f64::consts::PI;
+
+use crate::types::config::ResamplingQuality;
+
+// Filter phases kept for rates whose ratio needs more, e.g. 44056Hz. Common rates
+// (44.1k, 22.05k, 32k, 96k...) get one phase per output position.
+const MAX_PHASES: u64 = 1024;
+
+/// Converts interleaved stereo PCM between sample rates with a Kaiser windowed sinc
+/// lowpass, evaluated as a polyphase filter bank. State is kept between calls so chunk
+/// boundaries don't click.
+pub struct Resampler {
+    // One row of `taps` coefficients per phase
+    coefficients: Vec<f32>,
+    taps: usize,
+    phases: u64,
+    // Input frames advanced per output frame are `step / denominator`
+    step: u64,
+    denominator: u64,
+    // Position of the next output frame: input frame `index` plus `phase / denominator`
+    index: usize,
+    phase: u64,
+    // Input frames still needed by the filter, starting `taps / 2 - 1` frames before `index`
+    history: Vec<[f32; 2]>,
+}
+
+impl Resampler {
+    pub fn new(input_rate: u32, output_rate: u32, quality: ResamplingQuality) -> Result<Self, String> {
+        // A zero step would never move through the input
+        if input_rate == 0 || output_rate == 0 {
+            return Err(format!("Can't resample from {}Hz to {}Hz", input_rate, output_rate));
+        }
+        let divisor = gcd(input_rate as u64, output_rate as u64);
+        let step = input_rate as u64 / divisor;
+        let denominator = output_rate as u64 / divisor;
+        let phases = denominator.min(MAX_PHASES);
+
+        // Zero crossings on each side, cutoff as a fraction of the lower Nyquist frequency
+        // and Kaiser beta, chosen so the stopband starts at Nyquist. Roughly 50, 70 and
+        // 90dB of stopband attenuation.
+        let (zero_crossings, rolloff, beta) = match quality {
+            ResamplingQuality::Low => (8.0, 0.80, 5.0),
+            ResamplingQuality::Medium => (16.0, 0.86, 7.0),
+            ResamplingQuality::High => (32.0, 0.91, 9.0),
+        };
+        // Downsampling stretches the filter so it also cuts below the output's Nyquist
+        let scale = (output_rate as f64 / input_rate as f64).min(1.0);
+        let half = (zero_crossings / scale).ceil() as usize;
+        let taps = half * 2;
+        let cutoff = rolloff * scale;
+
+        let mut coefficients = Vec::with_capacity(phases as usize * taps);
+        for phase in 0..phases {
+            let offset = phase as f64 / phases as f64;
+            let row: Vec<f64> = (0..taps)
+                .map(|tap| {
+                    // Distance of this tap from the output position, in input frames
+                    let t = tap as f64 - (half - 1) as f64 - offset;
+                    cutoff * sinc(cutoff * t) * kaiser(t / half as f64, beta)
+                })
+                .collect();
+            // Unity gain at DC for every phase
+            let sum: f64 = row.iter().sum();
+            coefficients.extend(row.iter().map(|c| (c / sum) as f32));
+        }
+
+        Ok(Self {
+            coefficients,
+            taps,
+            phases,
+            step,
+            denominator,
+            index: half - 1,
+            phase: 0,
+            history: vec![[0.0; 2]; half - 1],
+        })
+    }
+
+    pub fn process(&mut self, input: &[i16]) -> Vec<i16> {
+        self.history
+            .extend(input.chunks_exact(2).map(|frame| [frame[0] as f32, frame[1] as f32]));
+
+        let half = self.taps / 2;
+        let mut output = Vec::with_capacity(
+            (input.len() as u64 * self.denominator / self.step) as usize + 2,
+        );
+        while self.index + half < self.history.len() {
+            let row = (self.phase * self.phases / self.denominator) as usize;
+            let coefficients = &self.coefficients[row * self.taps..(row + 1) * self.taps];
+            let frames = &self.history[self.index + 1 - half..=self.index + half];
+
+            let mut sum = [0.0f32; 2];
+            for (coefficient, frame) in coefficients.iter().zip(frames) {
+                sum[0] += coefficient * frame[0];
+                sum[1] += coefficient * frame[1];
+            }
+            output.push(sum[0].round() as i16);
+            output.push(sum[1].round() as i16);
+
+            self.phase += self.step;
+            self.index += (self.phase / self.denominator) as usize;
+            self.phase %= self.denominator;
+        }
+
+        // Drop frames that no future output reaches
+        let consumed = (self.index + 1).saturating_sub(half).min(self.history.len());
+        self.history.drain(..consumed);
+        self.index -= consumed;
+        output
+    }
+
+    /// Returns the output still held back for lookahead at the end of a stream.
+    pub fn flush(&mut self) -> Vec<i16> {
+        self.process(&vec![0; self.taps])
+    }
+}
+
+fn gcd(a: u64, b: u64) -> u64 {
+    if b == 0 { a } else { gcd(b, a % b) }
+}
+
+fn sinc(x: f64) -> f64 {
+    if x.abs() < 1e-9 {
+        1.0
+    } else {
+        (PI * x).sin() / (PI * x)
+    }
+}
+
+/// Kaiser window at `x` in -1..=1.
+fn kaiser(x: f64, beta: f64) -> f64 {
+    if x.abs() > 1.0 {
+        return 0.0;
+    }
+    bessel_i0(beta * (1.0 - x * x).sqrt()) / bessel_i0(beta)
+}
+
+/// Zeroth order modified Bessel function of the first kind, by its power series.
+fn bessel_i0(x: f64) -> f64 {
+    let mut sum = 1.0;
+    let mut term = 1.0;
+    let quarter_x_squared = x * x / 4.0;
+    for k in 1..50 {
+        term *= quarter_x_squared / (k * k) as f64;
+        sum += term;
+        if term < sum * 1e-12 {
+            break;
+        }
+    }
+    sum
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::playback::test_utils::thd_n;
+
+    const OUTPUT_RATE: u32 = 48_000;
+    const TONE: f64 = 1_000.0;
+
+    fn sine(rate: u32, seconds: f64) -> Vec<i16> {
+        (0..(rate as f64 * seconds) as usize)
+            .flat_map(|i| {
+                let sample = (16_000.0 * (2.0 * PI * TONE * i as f64 / rate as f64).sin()).round() as i16;
+                [sample, sample]
+            })
+            .collect()
+    }
+
+    #[test]
+    fn thd_n_of_a_1khz_sine_stays_within_each_quality_bound() {
+        // 16-bit output puts the floor at about -93dB
+        let bounds = [
+            (ResamplingQuality::Low, -55.0),
+            (ResamplingQuality::Medium, -75.0),
+            (ResamplingQuality::High, -85.0),
+        ];
+        for input_rate in [44_100, 22_050, 96_000] {
+            for (quality, bound) in bounds {
+                let mut resampler = Resampler::new(input_rate, OUTPUT_RATE, quality).unwrap();
+                // Fed in decoder-sized chunks so chunk boundaries are covered too
+                let mut output = Vec::new();
+                for chunk in sine(input_rate, 1.0).chunks(2 * 1152) {
+                    output.extend(resampler.process(chunk));
+                }
+                output.extend(resampler.flush());
+
+                // The filter's attack and release at either end aren't part of the measurement
+                let settled = &output[2 * 4800..output.len() - 2 * 4800];
+                let measured = thd_n(settled, TONE);
+                assert!(
+                    measured < bound,
+                    "{}Hz at {:?}: THD+N {:.1}dB, expected below {}dB",
+                    input_rate,
+                    quality,
+                    measured,
+                    bound
+                );
+            }
+        }
+    }
+
+    #[test]
+    fn keeps_the_length_of_the_input() {
+        for input_rate in [44_100, 22_050, 96_000] {
+            let mut resampler = Resampler::new(input_rate, OUTPUT_RATE, ResamplingQuality::High).unwrap();
+            let mut frames = resampler.process(&sine(input_rate, 1.0)).len() / 2;
+            frames += resampler.flush().len() / 2;
+            assert!(frames.abs_diff(OUTPUT_RATE as usize) <= 1, "{}Hz: {} frames", input_rate, frames);
+        }
+    }
+
+    #[test]
+    fn rejects_a_zero_rate() {
+        assert!(Resampler::new(0, OUTPUT_RATE, ResamplingQuality::High).is_err());
+        assert!(Resampler::new(44_100, 0, ResamplingQuality::High).is_err());
+    }
+}
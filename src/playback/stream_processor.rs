@@ -1,20 +1,206 @@
-use super::filters_manager::FiltersManager;
+use std::collections::VecDeque;
+use std::future::Future;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::mpsc::{self, Receiver, Sender, TryRecvError};
 use serde_json::Value;
+use tokio::runtime::Handle;
+
+use crate::types::config::ResamplingQuality;
+use super::decoder::{decode, DecodedChunk, SeekRequest, StreamChunk, StreamInput};
+use super::filters_manager::FiltersManager;
+use super::opus::{OpusDecoder, CHANNELS, FRAME_SIZE};
+use super::volume::Volume;
+
+/// Interleaved stereo samples in one 20ms frame at 48kHz.
+pub const FRAME_SAMPLES: usize = FRAME_SIZE * CHANNELS;
+// Decoded chunks held ahead of playback before the decoder thread blocks
+const DECODE_BUFFER_CHUNKS: usize = 64;
+
+pub enum FrameResult {
+    Frame(Vec<i16>),
+    /// An Opus packet that can be sent as is.
+    Packet(Vec<u8>),
+    /// Nothing decoded yet; the stream is still loading or the network is behind.
+    Buffering,
+    /// The last seek failed and the track goes on from where it was, `skipped` frames of
+    /// the track further on for the audio dropped in the meantime.
+    SeekFailed { error: String, skipped: f64 },
+    Finished,
+    Error(String),
+}
 
-// Placeholder for the resource
 pub struct AudioResource {
     pub filters: FiltersManager,
-    // stream: ...
+    pub volume: Volume,
+    receiver: Receiver<DecodedChunk>,
+    // Filtered PCM waiting to be framed
+    buffer: VecDeque<i16>,
+    eof: bool,
+    // Shared with the decoder thread
+    passthrough: Arc<AtomicBool>,
+    // Decodes packets that were passed through before processing was switched back on
+    opus_decoder: Option<OpusDecoder>,
+    seeks: Sender<SeekRequest>,
+    // Id of the last seek sent to the decoder, and whether it's still in progress
+    seek_id: u64,
+    seeking: bool,
+    // Track frames dropped since the seek, in case it fails
+    seek_skipped: f64,
+}
+
+impl AudioResource {
+    /// Pulls the next 20ms of filtered PCM, or an Opus packet while passing through.
+    /// The last frame of a track is padded with silence.
+    pub fn read_frame(&mut self) -> FrameResult {
+        while self.seeking {
+            match self.receiver.try_recv() {
+                Ok(Ok(StreamChunk::Seeked(id, result))) if id == self.seek_id => {
+                    self.seeking = false;
+                    if let Err(error) = result {
+                        return FrameResult::SeekFailed { error, skipped: self.seek_skipped };
+                    }
+                }
+                // Audio from before the seek
+                Ok(Ok(StreamChunk::Pcm(chunk))) => self.seek_skipped += (chunk.len() / CHANNELS) as f64,
+                Ok(Ok(StreamChunk::Opus(_))) => self.seek_skipped += FRAME_SIZE as f64,
+                Ok(Ok(_)) => {}
+                Ok(Err(e)) => {
+                    self.seeking = false;
+                    self.eof = true;
+                    return FrameResult::Error(e);
+                }
+                Err(TryRecvError::Empty) => return FrameResult::Buffering,
+                Err(TryRecvError::Disconnected) => {
+                    self.seeking = false;
+                    self.eof = true;
+                }
+            }
+        }
+
+        while !self.eof && self.buffer.len() < FRAME_SAMPLES {
+            match self.receiver.try_recv() {
+                Ok(Ok(StreamChunk::Pcm(chunk))) => {
+                    let processed = self.filters.process(&chunk, &mut self.volume);
+                    self.buffer.extend(processed);
+                }
+                Ok(Ok(StreamChunk::Opus(packet))) => {
+                    if self.passthrough.load(Ordering::Relaxed) {
+                        // Less than a frame of PCM is left from before passthrough started
+                        // and can't be lined up with whole packets
+                        self.buffer.clear();
+                        return FrameResult::Packet(packet);
+                    }
+                    let decoder = match &mut self.opus_decoder {
+                        Some(decoder) => decoder,
+                        None => match OpusDecoder::new() {
+                            Ok(decoder) => self.opus_decoder.insert(decoder),
+                            Err(e) => {
+                                self.eof = true;
+                                return FrameResult::Error(e);
+                            }
+                        },
+                    };
+                    match decoder.decode(&packet) {
+                        Ok(pcm) => {
+                            let processed = self.filters.process(pcm, &mut self.volume);
+                            self.buffer.extend(processed);
+                        }
+                        Err(e) => {
+                            self.eof = true;
+                            return FrameResult::Error(e);
+                        }
+                    }
+                }
+                // Left over from a seek that a later one replaced
+                Ok(Ok(StreamChunk::Seeked(..))) => {}
+                Ok(Ok(StreamChunk::End)) => self.eof = true,
+                Ok(Err(e)) => {
+                    self.eof = true;
+                    return FrameResult::Error(e);
+                }
+                Err(TryRecvError::Empty) => return FrameResult::Buffering,
+                Err(TryRecvError::Disconnected) => self.eof = true,
+            }
+        }
+
+        if self.buffer.is_empty() {
+            return FrameResult::Finished;
+        }
+        let take = self.buffer.len().min(FRAME_SAMPLES);
+        let mut frame: Vec<i16> = self.buffer.drain(..take).collect();
+        frame.resize(FRAME_SAMPLES, 0);
+        FrameResult::Frame(frame)
+    }
+
+    /// Continues the track from `position_ms`. Buffered audio is dropped, and reads count
+    /// as buffering until the decoder has moved there.
+    pub fn seek(&mut self, position_ms: u64) {
+        self.seek_id += 1;
+        if !self.seeking {
+            self.seek_skipped = 0.0;
+        }
+        // Buffered audio has been through the filters, which may have changed its tempo
+        self.seek_skipped += (self.buffer.len() / CHANNELS) as f64 * self.filters.track_speed() as f64;
+        self.buffer.clear();
+        self.filters.reset();
+        self.eof = false;
+        self.seeking = self.seeks.send((self.seek_id, position_ms)).is_ok();
+    }
+
+    /// Lets Opus packets skip decoding and re-encoding. Only valid while no volume or
+    /// filters have to be applied; switching it off takes effect from the next frame.
+    pub fn set_passthrough(&mut self, enabled: bool) {
+        self.passthrough.store(enabled, Ordering::Relaxed);
+    }
 }
 
+/// Starts fetching and decoding a track in the background. `stream` resolves
+/// where to read from.
 pub fn create_audio_resource(
-    _track_info: &Value, // Info object
+    track_info: &Value, // Info object
     initial_filters: &Value,
-) -> AudioResource {
+    initial_volume: u32,
+    resampling_quality: ResamplingQuality,
+    stream: impl Future<Output = Result<StreamInput, String>> + Send + 'static,
+) -> Result<AudioResource, String> {
+    if !track_info.is_object() {
+        return Err("Track info is missing".to_string());
+    }
+
     let mut filters_manager = FiltersManager::new();
     filters_manager.update(initial_filters);
-    
-    AudioResource {
+
+    let (sender, receiver) = mpsc::sync_channel(DECODE_BUFFER_CHUNKS);
+    let passthrough = Arc::new(AtomicBool::new(false));
+    let decoder_passthrough = passthrough.clone();
+    let (seeks, seek_receiver) = mpsc::channel();
+    tokio::spawn(async move {
+        let input = match stream.await {
+            Ok(input) => input,
+            Err(e) => {
+                let _ = sender.send(Err(e));
+                return;
+            }
+        };
+        let handle = Handle::current();
+        let _ = tokio::task::spawn_blocking(move || {
+            decode(input, handle, resampling_quality, decoder_passthrough, seek_receiver, sender)
+        })
+        .await;
+    });
+
+    Ok(AudioResource {
         filters: filters_manager,
-    }
+        volume: Volume::new(initial_volume),
+        receiver,
+        buffer: VecDeque::new(),
+        eof: false,
+        passthrough,
+        opus_decoder: None,
+        seeks,
+        seek_id: 0,
+        seeking: false,
+        seek_skipped: 0.0,
+    })
 }
@@ -0,0 +1,57 @@
+//! Test signals and measurements shared by the DSP tests. Audio is interleaved stereo at
+//! 48kHz, like the player's.
+
+use std::f64::consts::PI;
+
+pub const RATE: f64 = 48000.0;
+
+/// `frames` of a sine at `frequency`, the same on both channels.
+pub fn sine(frequency: f64, frames: usize) -> Vec<f32> {
+    sweep(frequency, frequency, frames)
+}
+
+/// `frames` of a sine gliding linearly from `from` to `to`, the same on both channels.
+pub fn sweep(from: f64, to: f64, frames: usize) -> Vec<f32> {
+    let slope = (to - from) / (frames as f64 / RATE);
+    (0..frames)
+        .flat_map(|i| {
+            let t = i as f64 / RATE;
+            let sample = (8000.0 * (2.0 * PI * (from * t + slope * t * t / 2.0)).sin()) as f32;
+            [sample, sample]
+        })
+        .collect()
+}
+
+/// Frequency of the left channel, counted from its rising zero crossings.
+pub fn frequency(samples: &[f32]) -> f64 {
+    let left: Vec<f32> = samples.iter().step_by(2).copied().collect();
+    let crossings: Vec<usize> = (1..left.len()).filter(|&i| left[i - 1] < 0.0 && left[i] >= 0.0).collect();
+    let (first, last) = (crossings[0], crossings[crossings.len() - 1]);
+    (crossings.len() - 1) as f64 * RATE / (last - first) as f64
+}
+
+/// THD+N in dB: what's left of the left channel after removing the tone at `tone` Hz,
+/// relative to that tone. Only whole periods are measured, which keeps the tone's sine,
+/// cosine and DC parts independent so each can be projected out on its own.
+pub fn thd_n(pcm: &[i16], tone: f64) -> f64 {
+    let period = (RATE / tone) as usize;
+    let samples: Vec<f64> = pcm.iter().step_by(2).map(|&s| s as f64).collect();
+    let samples = &samples[..samples.len() / period * period];
+    let w = 2.0 * PI * tone / RATE;
+    let n = samples.len() as f64;
+
+    let project = |f: &dyn Fn(f64) -> f64| {
+        samples.iter().enumerate().map(|(i, s)| s * f(w * i as f64)).sum::<f64>() * 2.0 / n
+    };
+    let a = project(&f64::sin);
+    let b = project(&f64::cos);
+    let dc = samples.iter().sum::<f64>() / n;
+
+    let residual: f64 = samples
+        .iter()
+        .enumerate()
+        .map(|(i, s)| (s - a * (w * i as f64).sin() - b * (w * i as f64).cos() - dc).powi(2))
+        .sum();
+    let power = (a * a + b * b) / 2.0 * n;
+    10.0 * (residual / power).log10()
+}
@@ -0,0 +1,70 @@
+use super::SAMPLE_RATE;
+
+/// Frames over which a volume change is spread, 5ms.
+const RAMP_FRAMES: f32 = SAMPLE_RATE / 1000.0 * 5.0;
+// Level above which boosted samples are bent towards full scale instead of clipping
+const LIMITER_THRESHOLD: f32 = 0.8 * i16::MAX as f32;
+
+/// Player volume as the last stage of the f32 bus, on interleaved stereo in the 16-bit range.
+/// Changes ramp linearly so they don't click, and gains above 100% go through a soft limiter.
+pub struct Volume {
+    gain: f32,
+    target: f32,
+    // Gain change per frame while ramping
+    step: f32,
+}
+
+impl Volume {
+    pub fn new(percent: u32) -> Self {
+        let gain = percent as f32 / 100.0;
+        Self {
+            gain,
+            target: gain,
+            step: 0.0,
+        }
+    }
+
+    /// Ramps to `percent` from the current gain, continuing from where a running ramp is.
+    pub fn set(&mut self, percent: u32) {
+        self.target = percent as f32 / 100.0;
+        self.step = (self.target - self.gain) / RAMP_FRAMES;
+    }
+
+    /// Whether it's at 100% with no ramp running, so it leaves the audio untouched.
+    pub fn is_unity(&self) -> bool {
+        self.gain == 1.0 && self.target == 1.0
+    }
+
+    pub fn process(&mut self, pcm: &mut [f32]) {
+        if self.is_unity() {
+            return;
+        }
+        let limit = self.gain > 1.0 || self.target > 1.0;
+
+        for frame in pcm.chunks_exact_mut(2) {
+            if self.gain != self.target {
+                self.gain += self.step;
+                let reached = if self.step > 0.0 { self.gain >= self.target } else { self.gain <= self.target };
+                if reached {
+                    self.gain = self.target;
+                }
+            }
+            for sample in frame {
+                let scaled = *sample * self.gain;
+                *sample = if limit { soft_limit(scaled) } else { scaled };
+            }
+        }
+    }
+}
+
+/// Passes samples below the threshold unchanged and compresses the rest with a tanh
+/// curve that approaches full scale, keeping the transfer curve smooth at the threshold.
+fn soft_limit(sample: f32) -> f32 {
+    let magnitude = sample.abs();
+    if magnitude <= LIMITER_THRESHOLD {
+        return sample;
+    }
+    let headroom = i16::MAX as f32 - LIMITER_THRESHOLD;
+    let limited = LIMITER_THRESHOLD + headroom * ((magnitude - LIMITER_THRESHOLD) / headroom).tanh();
+    limited.copysign(sample)
+}
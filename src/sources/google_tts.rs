@@ -7,7 +7,11 @@ pub struct GoogleTtsSource;
 
 #[async_trait]
 impl Source for GoogleTtsSource {
-    async fn search(&self, _query: &str, _nodelink: &NodelinkMock) -> Value {
+    fn search_terms(&self) -> &'static [&'static str] {
+        &["speak", "gtts"]
+    }
+
+    async fn search(&self, _query: &str, _search_term: &str, _nodelink: &NodelinkMock) -> Value {
         json!({ "loadType": "empty", "data": {} })
     }
 
@@ -1,5 +1,6 @@
 use async_trait::async_trait;
 use serde_json::{json, Value};
+use crate::playback::decoder::StreamInput;
 use crate::types::http::NodelinkMock;
 use super::Source;
 
@@ -7,11 +8,72 @@ pub struct HttpSource;
 
 #[async_trait]
 impl Source for HttpSource {
-    async fn search(&self, _query: &str, _nodelink: &NodelinkMock) -> Value {
+    async fn search(&self, _query: &str, _search_term: &str, _nodelink: &NodelinkMock) -> Value {
         json!({ "loadType": "empty", "data": {} })
     }
 
     async fn resolve(&self, _url: &str, _nodelink: &NodelinkMock) -> Value {
         json!({ "loadType": "empty", "data": {} })
     }
+
+    async fn get_stream(&self, info: &Value, _nodelink: &NodelinkMock) -> Result<StreamInput, String> {
+        match info["uri"].as_str() {
+            Some(uri) if uri.starts_with("http://") || uri.starts_with("https://") => {
+                let path = uri.split(['?', '#']).next().unwrap_or(uri);
+                if path.ends_with(".m3u8") {
+                    Ok(StreamInput::Hls(uri.to_string()))
+                } else {
+                    Ok(StreamInput::Http(uri.to_string()))
+                }
+            }
+            Some(_) => Err("Track uri is not an http(s) URL".to_string()),
+            None => Err("Track has no uri to stream from".to_string()),
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::managers::source_manager::SourceManager;
+    use crate::types::config::{Config, SourceConfig};
+
+    #[tokio::test]
+    async fn only_streams_http_urls() {
+        let nodelink = NodelinkMock { route_planner: None };
+        for uri in ["/etc/passwd", "file:///etc/passwd", "C:\\Windows\\win.ini", "ftp://example.com/a.mp3"] {
+            let info = json!({ "sourceName": "http", "uri": uri });
+            assert!(HttpSource.get_stream(&info, &nodelink).await.is_err(), "{}", uri);
+        }
+
+        let info = json!({ "sourceName": "http", "uri": "https://example.com/a.mp3" });
+        assert!(matches!(
+            HttpSource.get_stream(&info, &nodelink).await,
+            Ok(StreamInput::Http(url)) if url == "https://example.com/a.mp3"
+        ));
+
+        let info = json!({ "sourceName": "http", "uri": "https://example.com/live/index.m3u8?token=a" });
+        assert!(matches!(
+            HttpSource.get_stream(&info, &nodelink).await,
+            Ok(StreamInput::Hls(url)) if url == "https://example.com/live/index.m3u8?token=a"
+        ));
+    }
+
+    #[tokio::test]
+    async fn local_files_are_refused_unless_enabled() {
+        let nodelink = NodelinkMock { route_planner: None };
+        let info = json!({ "sourceName": "local", "uri": "/etc/passwd" });
+        let mut config = Config::default();
+        assert!(SourceManager::new(&config).stream(&info, &nodelink).await.is_err());
+
+        // Listing it isn't enough, it has to be switched on
+        config.sources.insert("local".to_string(), SourceConfig::default());
+        assert!(SourceManager::new(&config).stream(&info, &nodelink).await.is_err());
+
+        config.sources.insert("local".to_string(), SourceConfig { enabled: Some(true) });
+        assert!(matches!(
+            SourceManager::new(&config).stream(&info, &nodelink).await,
+            Ok(StreamInput::File(path)) if path.to_str() == Some("/etc/passwd")
+        ));
+    }
 }
@@ -7,7 +7,7 @@ pub struct InstagramSource;
 
 #[async_trait]
 impl Source for InstagramSource {
-    async fn search(&self, _query: &str, _nodelink: &NodelinkMock) -> Value {
+    async fn search(&self, _query: &str, _search_term: &str, _nodelink: &NodelinkMock) -> Value {
         json!({ "loadType": "empty", "data": {} })
     }
 
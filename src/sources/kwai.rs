@@ -7,7 +7,7 @@ pub struct KwaiSource;
 
 #[async_trait]
 impl Source for KwaiSource {
-    async fn search(&self, _query: &str, _nodelink: &NodelinkMock) -> Value {
+    async fn search(&self, _query: &str, _search_term: &str, _nodelink: &NodelinkMock) -> Value {
         json!({ "loadType": "empty", "data": {} })
     }
 
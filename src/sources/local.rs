@@ -1,5 +1,7 @@
 use async_trait::async_trait;
 use serde_json::{json, Value};
+use std::path::PathBuf;
+use crate::playback::decoder::StreamInput;
 use crate::types::http::NodelinkMock;
 use super::Source;
 
@@ -7,11 +9,19 @@ pub struct LocalSource;
 
 #[async_trait]
 impl Source for LocalSource {
-    async fn search(&self, _query: &str, _nodelink: &NodelinkMock) -> Value {
+    async fn search(&self, _query: &str, _search_term: &str, _nodelink: &NodelinkMock) -> Value {
         json!({ "loadType": "empty", "data": {} })
     }
 
     async fn resolve(&self, _url: &str, _nodelink: &NodelinkMock) -> Value {
         json!({ "loadType": "empty", "data": {} })
     }
+
+    async fn get_stream(&self, info: &Value, _nodelink: &NodelinkMock) -> Result<StreamInput, String> {
+        let uri = info["uri"]
+            .as_str()
+            .ok_or_else(|| "Track has no uri to stream from".to_string())?;
+        let path = uri.strip_prefix("file://").unwrap_or(uri);
+        Ok(StreamInput::File(PathBuf::from(path)))
+    }
 }
@@ -1,5 +1,6 @@
 use async_trait::async_trait;
 use serde_json::Value;
+use crate::playback::decoder::StreamInput;
 use crate::types::http::NodelinkMock;
 
 pub mod youtube;
@@ -20,7 +21,15 @@ pub mod tidal;
 
 #[async_trait]
 pub trait Source: Send + Sync {
-    async fn search(&self, query: &str, nodelink: &NodelinkMock) -> Value;
+    /// Search prefixes handled by this source, without the trailing colon (e.g. `scsearch`).
+    fn search_terms(&self) -> &'static [&'static str] {
+        &[]
+    }
+    async fn search(&self, query: &str, search_term: &str, nodelink: &NodelinkMock) -> Value;
     async fn resolve(&self, url: &str, nodelink: &NodelinkMock) -> Value;
-    // ... other methods like load_stream
+    /// Where to read the audio of a track this source loaded. Track info comes from clients,
+    /// so only sources that stream local files may return `StreamInput::File`.
+    async fn get_stream(&self, _info: &Value, _nodelink: &NodelinkMock) -> Result<StreamInput, String> {
+        Err("This source can't stream tracks yet".to_string())
+    }
 }
@@ -7,7 +7,11 @@ pub struct SoundcloudSource;
 
 #[async_trait]
 impl Source for SoundcloudSource {
-    async fn search(&self, _query: &str, _nodelink: &NodelinkMock) -> Value {
+    fn search_terms(&self) -> &'static [&'static str] {
+        &["scsearch"]
+    }
+
+    async fn search(&self, _query: &str, _search_term: &str, _nodelink: &NodelinkMock) -> Value {
         json!({ "loadType": "empty", "data": {} })
     }
 
@@ -96,8 +96,5 @@ pub fn build_track(
         Err(_) => return None,
     };
 
-    Some(DecodedTrack {
-        encoded,
-        info: track_info,
-    })
+    Some(DecodedTrack::new(encoded, track_info))
 }
@@ -47,8 +47,15 @@ impl YoutubeSource {
 
 #[async_trait]
 impl Source for YoutubeSource {
-    async fn search(&self, query: &str, nodelink: &NodelinkMock) -> Value {
-        self.android.search(query, nodelink).await
+    fn search_terms(&self) -> &'static [&'static str] {
+        &["ytsearch", "ytmsearch"]
+    }
+
+    async fn search(&self, query: &str, search_term: &str, nodelink: &NodelinkMock) -> Value {
+        match search_term {
+            "ytmsearch" => self.music.search(query, nodelink).await,
+            _ => self.android.search(query, nodelink).await,
+        }
     }
 
     async fn resolve(&self, _url: &str, _nodelink: &NodelinkMock) -> Value {
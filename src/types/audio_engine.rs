@@ -1,5 +1,7 @@
 use tokio::sync::mpsc;
 use serde_json::Value;
+use crate::types::config::Config;
+use crate::types::stats::RustlinkStats;
 
 #[derive(Debug)]
 pub enum AudioEngineCommand {
@@ -11,10 +13,16 @@ pub enum AudioEngineCommand {
         resp: mpsc::Sender<Value>,
     },
     DestroyPlayer {
+        session_id: String,
         guild_id: String,
         resp: mpsc::Sender<Value>,
     },
+    DestroySessionPlayers {
+        session_id: String,
+        resp: mpsc::Sender<Value>,
+    },
     PlayerCommand {
+        session_id: String,
         guild_id: String,
         command: String,
         args: Vec<Value>,
@@ -32,10 +40,26 @@ pub enum AudioEngineCommand {
         resp: mpsc::Sender<Vec<String>>,
     },
     GetPlayer {
+        session_id: String,
         guild_id: String,
         resp: mpsc::Sender<Value>,
     },
     GetPlayers {
+        session_id: String,
         resp: mpsc::Sender<Vec<Value>>,
     },
+    GetStats {
+        resp: mpsc::Sender<RustlinkStats>,
+    },
+    ReloadConfig {
+        config: Box<Config>,
+        resp: mpsc::Sender<Value>,
+    },
+}
+
+/// A WebSocket `event` payload emitted by a player, routed to the session that owns it.
+#[derive(Debug)]
+pub struct PlayerEvent {
+    pub session_id: String,
+    pub payload: Value,
 }
@@ -0,0 +1,183 @@
+use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
+
+use super::logger::LoggingConfig;
+
+/// Settings loaded from the config file at startup. Every section falls back to its
+/// defaults, so an empty `{}` file is a valid config.
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct Config {
+    pub server: ServerConfig,
+    pub logging: LoggingConfig,
+    /// Seconds between `playerUpdate` ops.
+    pub player_update_interval: u64,
+    /// Seconds between `stats` ops.
+    pub stats_interval: u64,
+    pub dos_protection: DosProtectionConfig,
+    pub rate_limit: RateLimitConfig,
+    pub connection: ConnectionConfig,
+    pub route_planner: RoutePlannerConfig,
+    pub audio: AudioConfig,
+    /// Per-source settings keyed by source name. Sources not listed are enabled, except the
+    /// ones in `OPT_IN_SOURCES`.
+    pub sources: HashMap<String, SourceConfig>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            server: ServerConfig::default(),
+            logging: LoggingConfig::default(),
+            player_update_interval: 5,
+            stats_interval: 60,
+            dos_protection: DosProtectionConfig::default(),
+            rate_limit: RateLimitConfig::default(),
+            connection: ConnectionConfig::default(),
+            route_planner: RoutePlannerConfig::default(),
+            audio: AudioConfig::default(),
+            sources: HashMap::new(),
+        }
+    }
+}
+
+/// Sources that stay off unless their `enabled` is set to `true`. `local` streams any file
+/// the node can read, from a path the client chooses.
+const OPT_IN_SOURCES: &[&str] = &["local"];
+
+impl Config {
+    pub fn is_source_enabled(&self, name: &str) -> bool {
+        let enabled = self.sources.get(name).and_then(|source| source.enabled);
+        enabled.unwrap_or(!OPT_IN_SOURCES.contains(&name))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct ServerConfig {
+    pub host: String,
+    pub port: u16,
+    /// Any of these is accepted in the `Authorization` header.
+    pub passwords: Vec<String>,
+    /// Whether `/version` can be requested without a password.
+    pub public_version: bool,
+}
+
+impl Default for ServerConfig {
+    fn default() -> Self {
+        Self {
+            host: "0.0.0.0".to_string(),
+            port: 8080,
+            passwords: vec!["youshallnotpass".to_string()],
+            public_version: true,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct DosProtectionConfig {
+    pub enabled: bool,
+    /// Requests allowed per IP within `time_window_ms` before it gets blocked.
+    pub threshold: u32,
+    pub time_window_ms: u64,
+    pub block_duration_ms: u64,
+}
+
+impl Default for DosProtectionConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            threshold: 100,
+            time_window_ms: 10_000,
+            block_duration_ms: 60_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct RateLimitConfig {
+    pub enabled: bool,
+    pub max_requests: u32,
+    pub time_window_ms: u64,
+}
+
+impl Default for RateLimitConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            max_requests: 120,
+            time_window_ms: 60_000,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct ConnectionConfig {
+    /// Milliseconds between connection checks, `0` disables them.
+    pub interval_ms: u64,
+}
+
+impl Default for ConnectionConfig {
+    fn default() -> Self {
+        Self { interval_ms: 300_000 }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct RoutePlannerConfig {
+    /// CIDR blocks to rotate outgoing requests through, e.g. `2001:db8::/64`.
+    pub ip_blocks: Vec<String>,
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct AudioConfig {
+    pub opus: OpusConfig,
+    /// Resampler used for sources that aren't 48kHz.
+    pub resampling_quality: ResamplingQuality,
+}
+
+/// Filter length of the resampler, trading CPU for less aliasing and a flatter passband.
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum ResamplingQuality {
+    Low,
+    Medium,
+    #[default]
+    High,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, rename_all = "camelCase", deny_unknown_fields)]
+pub struct OpusConfig {
+    /// Bits per second, 500 to 512000.
+    pub bitrate: u32,
+    /// Encoder effort from 0 (fastest) to 10 (best quality).
+    pub complexity: u8,
+    /// In-band forward error correction, lets receivers recover a lost packet from the next one.
+    pub fec: bool,
+    /// Expected packet loss the encoder should prepare for, 0 to 100.
+    pub packet_loss_percent: u8,
+}
+
+impl Default for OpusConfig {
+    fn default() -> Self {
+        Self {
+            bitrate: 128_000,
+            complexity: 10,
+            fec: true,
+            packet_loss_percent: 5,
+        }
+    }
+}
+
+#[derive(Debug, Clone, Default, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
+pub struct SourceConfig {
+    /// Unset leaves the source at its default, see `Config::is_source_enabled`.
+    pub enabled: Option<bool>,
+}
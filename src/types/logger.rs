@@ -1,12 +1,33 @@
+use serde::{Deserialize, Serialize};
+
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct FileConfig {
     pub enabled: bool,
     pub path: Option<String>,
 }
 
+impl Default for FileConfig {
+    fn default() -> Self {
+        Self {
+            enabled: false,
+            path: Some("logs".to_string()),
+        }
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize)]
+#[serde(default, deny_unknown_fields)]
 pub struct LoggingConfig {
     pub file: Option<FileConfig>,
     pub level: Option<String>,
 }
-pub struct Config {
-    pub logging: Option<LoggingConfig>,
+
+impl Default for LoggingConfig {
+    fn default() -> Self {
+        Self {
+            file: None,
+            level: Some("info".to_string()),
+        }
+    }
 }
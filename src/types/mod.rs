@@ -1,4 +1,5 @@
 pub mod client;
+pub mod config;
 pub mod http;
 pub mod logger;
 pub mod stats;
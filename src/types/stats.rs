@@ -1,20 +1,33 @@
 use serde::{Deserialize, Serialize};
+use crate::managers::dos_protection_manager::DosProtectionManager;
+use crate::managers::rate_limit_manager::RateLimitManager;
 use crate::managers::session_manager::SessionManager;
+use crate::types::audio_engine::AudioEngineCommand;
+use crate::types::config::Config;
+use tokio::sync::mpsc;
 
 #[derive(Serialize, Deserialize, Debug, Clone)]
 pub struct RustlinkStats {
     pub players: usize,
     #[serde(rename = "playingPlayers")]
     pub playing_players: usize,
+    #[serde(rename = "frameStats")]
+    pub frame_stats: Option<FrameStats>,
 }
 
 pub struct RustlinkMock {
     pub statistics: RustlinkStats,
     pub sessions: SessionManager,
+    pub audio_engine: mpsc::Sender<AudioEngineCommand>,
+    pub dos_protection: DosProtectionManager,
+    pub rate_limit: RateLimitManager,
+    pub config: Config,
+    /// Where `config` was loaded from, read again on reload.
+    pub config_path: String,
 }
 
 // Estructuras de Salida (DTOs)
-#[derive(Debug, Serialize)]
+#[derive(Debug, Serialize, Deserialize, Clone)]
 pub struct FrameStats {
     pub sent: u64,
     pub nulled: u64,
@@ -31,6 +44,7 @@ pub struct MemoryStats {
 }
 
 #[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct CpuStats {
     pub cores: usize,
     pub system_load: f64,
@@ -38,6 +52,7 @@ pub struct CpuStats {
 }
 
 #[derive(Debug, Serialize)]
+#[serde(rename_all = "camelCase")]
 pub struct Stats {
     pub players: u32,
     pub playing_players: u32,
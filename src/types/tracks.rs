@@ -1,6 +1,8 @@
 use serde::{Deserialize, Serialize};
+use serde_json::Value;
 
 #[derive(Debug, Clone, Serialize, Deserialize)] // Clone es útil si necesitas copias
+#[serde(rename_all = "camelCase")]
 pub struct TrackInfo {
     pub title: String,
     pub author: String,
@@ -16,7 +18,27 @@ pub struct TrackInfo {
 }
 
 #[derive(Debug, Serialize, Deserialize)]
+#[serde(rename_all = "camelCase")]
 pub struct DecodedTrack {
     pub encoded: String,
     pub info: TrackInfo,
+    #[serde(default = "empty_object")]
+    pub plugin_info: Value,
+    #[serde(default = "empty_object")]
+    pub user_data: Value,
+}
+
+fn empty_object() -> Value {
+    Value::Object(Default::default())
+}
+
+impl DecodedTrack {
+    pub fn new(encoded: String, info: TrackInfo) -> Self {
+        Self {
+            encoded,
+            info,
+            plugin_info: empty_object(),
+            user_data: empty_object(),
+        }
+    }
 }
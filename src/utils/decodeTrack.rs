@@ -45,9 +45,9 @@ pub fn decode_track(encoded: &str) -> Result<DecodedTrack, Box<dyn std::error::E
     };
     let source_name = read_utf(&mut reader)?;
     let position = reader.read_i64::<BigEndian>()?;
-    Ok(DecodedTrack {
-        encoded: encoded.to_string(),
-        info: TrackInfo {
+    Ok(DecodedTrack::new(
+        encoded.to_string(),
+        TrackInfo {
             title,
             author,
             length,
@@ -60,5 +60,5 @@ pub fn decode_track(encoded: &str) -> Result<DecodedTrack, Box<dyn std::error::E
             source_name,
             position,
         },
-    })
+    ))
 }
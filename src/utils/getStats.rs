@@ -1,22 +1,25 @@
-use crate::types::stats::{CpuStats, FrameStats, MemoryStats, RustlinkMock, Stats};
+use crate::types::audio_engine::AudioEngineCommand;
+use crate::types::stats::{CpuStats, MemoryStats, RustlinkMock, Stats};
+use std::sync::{Arc, RwLock};
 use sysinfo::{System, SystemExt};
+use tokio::sync::mpsc;
+
+/// Refreshes `rustlink.statistics` with the player counts and frame stats of the AudioEngine.
+pub async fn update_statistics(rustlink: &Arc<RwLock<RustlinkMock>>) {
+    let audio_engine = rustlink.read().unwrap().audio_engine.clone();
+    let (tx, mut rx) = mpsc::channel(1);
+    if audio_engine.send(AudioEngineCommand::GetStats { resp: tx }).await.is_err() {
+        return;
+    }
+    if let Some(statistics) = rx.recv().await {
+        rustlink.write().unwrap().statistics = statistics;
+    }
+}
 
 pub fn get_stats(rustlink: &RustlinkMock) -> Stats {
     let players = rustlink.statistics.players;
     let playing_players = rustlink.statistics.playing_players;
-
-    let mut frame_stats = if players > 0 {
-        Some(FrameStats {
-            sent: 0,
-            nulled: 0,
-            deficit: 0,
-            expected: 0,
-        })
-    } else {
-        None
-    };
-
-    // Note: session iteration logic stubbed as we can't easily iterate players in AudioEngine synchronously
+    let frame_stats = rustlink.statistics.frame_stats.clone();
 
     let mut sys = System::new_all();
     sys.refresh_all();
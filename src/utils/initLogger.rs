@@ -1,4 +1,5 @@
-use super::types::logger::{Config, LoggingConfig};
+use super::logger::{set_log_level, LOG_FILE};
+use super::types::logger::LoggingConfig;
 use chrono::Utc;
 use rand::random;
 use std::fs::{self, File};
@@ -45,11 +46,11 @@ pub fn init_file_logger(config: &LoggingConfig) -> Option<File> {
     Some(file)
 }
 
-pub fn init_logger(config: Config) -> Option<File> {
-    let logging_config = config.logging.unwrap_or(LoggingConfig {
-        file: None,
-        level: Some("info".to_string()),
-    });
+pub fn init_logger(config: &LoggingConfig) {
+    set_log_level(config.level.as_deref().unwrap_or("info"));
 
-    init_file_logger(&logging_config)
+    let file = init_file_logger(config);
+    if let Ok(mut file_lock) = LOG_FILE.lock() {
+        *file_lock = file;
+    }
 }
@@ -0,0 +1,181 @@
+use super::types::config::Config;
+use serde_json::Value;
+use std::fs;
+use std::net::IpAddr;
+
+const ENV_PREFIX: &str = "RUSTLINK_";
+const LOG_LEVELS: [&str; 4] = ["debug", "info", "warn", "error"];
+
+/// Loads the JSON config at `path`, applies `RUSTLINK_*` environment overrides and
+/// validates the result. A missing file yields the defaults. Errors point at the
+/// offending line of the file, or at the environment variable that caused them.
+pub fn load_config(path: &str) -> Result<Config, String> {
+    let source = match fs::read_to_string(path) {
+        Ok(source) => source,
+        Err(e) if e.kind() == std::io::ErrorKind::NotFound => "{}".to_string(),
+        Err(e) => return Err(format!("{}: {}", path, e)),
+    };
+
+    let config: Config = serde_json::from_str(&source).map_err(|e| {
+        format!("{}:{}:{}: {}", path, e.line(), e.column(), strip_position(&e.to_string()))
+    })?;
+
+    let config = apply_env_overrides(config, std::env::vars())?;
+
+    validate(&config).map_err(|(key_path, message)| {
+        match line_of(&source, &key_path) {
+            Some(line) => format!("{}:{}: {}", path, line, message),
+            None => format!("{}: {}", path, message),
+        }
+    })?;
+
+    Ok(config)
+}
+
+// serde_json appends " at line X column Y", which is already part of our prefix.
+fn strip_position(message: &str) -> &str {
+    message.rsplit_once(" at line ").map_or(message, |(message, _)| message)
+}
+
+/// `RUSTLINK_SERVER__PORT=9000` sets `server.port`. Segments are matched against the
+/// config keys ignoring case and underscores, so `RUSTLINK_DOS_PROTECTION__ENABLED`
+/// maps to `dosProtection.enabled`. Values are parsed as JSON, falling back to a string.
+fn apply_env_overrides(
+    config: Config,
+    vars: impl Iterator<Item = (String, String)>,
+) -> Result<Config, String> {
+    let mut overrides: Vec<(String, String)> = vars
+        .filter(|(key, _)| key.starts_with(ENV_PREFIX) && key != "RUSTLINK_CONFIG")
+        .collect();
+    if overrides.is_empty() {
+        return Ok(config);
+    }
+    overrides.sort();
+
+    let mut value = serde_json::to_value(&config).map_err(|e| e.to_string())?;
+    for (key, raw) in &overrides {
+        let segments: Vec<&str> = key[ENV_PREFIX.len()..].split("__").collect();
+        set_path(&mut value, &segments, raw).map_err(|e| format!("{}: {}", key, e))?;
+
+        // Deserialize after each override so errors name the variable that caused them
+        serde_json::from_value::<Config>(value.clone()).map_err(|e| format!("{}: {}", key, e))?;
+    }
+
+    serde_json::from_value(value).map_err(|e| e.to_string())
+}
+
+fn set_path(value: &mut Value, segments: &[&str], raw: &str) -> Result<(), String> {
+    let normalize = |s: &str| s.replace('_', "").to_lowercase();
+
+    let mut current = value;
+    for segment in segments {
+        // Optional sections like `logging.file` default to null
+        if current.is_null() {
+            *current = Value::Object(Default::default());
+        }
+        let object = current
+            .as_object_mut()
+            .ok_or_else(|| "cannot set a field on a non-object value".to_string())?;
+        let key = object
+            .keys()
+            .find(|k| normalize(k) == normalize(segment))
+            .cloned()
+            .unwrap_or_else(|| segment.to_lowercase());
+        current = object.entry(key).or_insert(Value::Object(Default::default()));
+    }
+
+    let parsed = serde_json::from_str::<Value>(raw).unwrap_or_else(|_| Value::String(raw.to_string()));
+    *current = match (&current, parsed) {
+        // Lists can be given as `a,b,c`
+        (Value::Array(_), Value::String(s)) => s.split(',').map(|p| Value::String(p.trim().to_string())).collect(),
+        (Value::Array(_), Value::Number(n)) => Value::Array(vec![Value::String(n.to_string())]),
+        // Keep strings that happen to look like numbers or booleans as strings
+        (Value::String(_), parsed) if !parsed.is_string() => Value::String(raw.to_string()),
+        (_, parsed) => parsed,
+    };
+    Ok(())
+}
+
+fn validate(config: &Config) -> Result<(), (Vec<&'static str>, String)> {
+    let fail = |path: &[&'static str], message: &str| Err((path.to_vec(), message.to_string()));
+
+    if config.server.port == 0 {
+        return fail(&["server", "port"], "server.port must be between 1 and 65535");
+    }
+    if config.server.host.parse::<IpAddr>().is_err() {
+        return fail(&["server", "host"], "server.host must be an IP address");
+    }
+    if config.server.passwords.is_empty() || config.server.passwords.iter().any(|p| p.is_empty()) {
+        return fail(&["server", "passwords"], "server.passwords must contain at least one non-empty password");
+    }
+    if config.player_update_interval == 0 {
+        return fail(&["playerUpdateInterval"], "playerUpdateInterval must be at least 1 second");
+    }
+    if config.stats_interval == 0 {
+        return fail(&["statsInterval"], "statsInterval must be at least 1 second");
+    }
+    if let Some(level) = &config.logging.level
+        && !LOG_LEVELS.contains(&level.as_str())
+    {
+        return fail(
+            &["logging", "level"],
+            &format!("logging.level must be one of {}, got \"{}\"", LOG_LEVELS.join(", "), level),
+        );
+    }
+    let dos = &config.dos_protection;
+    if dos.enabled && (dos.threshold == 0 || dos.time_window_ms == 0) {
+        return fail(
+            &["dosProtection"],
+            "dosProtection.threshold and dosProtection.timeWindowMs must be greater than 0",
+        );
+    }
+    let rate_limit = &config.rate_limit;
+    if rate_limit.enabled && (rate_limit.max_requests == 0 || rate_limit.time_window_ms == 0) {
+        return fail(
+            &["rateLimit"],
+            "rateLimit.maxRequests and rateLimit.timeWindowMs must be greater than 0",
+        );
+    }
+    let opus = &config.audio.opus;
+    if !(500..=512_000).contains(&opus.bitrate) {
+        return fail(&["audio", "opus", "bitrate"], "audio.opus.bitrate must be between 500 and 512000");
+    }
+    if opus.complexity > 10 {
+        return fail(&["audio", "opus", "complexity"], "audio.opus.complexity must be between 0 and 10");
+    }
+    if opus.packet_loss_percent > 100 {
+        return fail(
+            &["audio", "opus", "packetLossPercent"],
+            "audio.opus.packetLossPercent must be between 0 and 100",
+        );
+    }
+    if let Some(block) = config.route_planner.ip_blocks.iter().find(|b| !is_valid_cidr(b)) {
+        return fail(
+            &["routePlanner", "ipBlocks"],
+            &format!("routePlanner.ipBlocks contains an invalid CIDR block \"{}\"", block),
+        );
+    }
+    Ok(())
+}
+
+fn is_valid_cidr(block: &str) -> bool {
+    let Some((address, prefix)) = block.split_once('/') else {
+        return false;
+    };
+    match (address.parse::<IpAddr>(), prefix.parse::<u8>()) {
+        (Ok(IpAddr::V4(_)), Ok(prefix)) => prefix <= 32,
+        (Ok(IpAddr::V6(_)), Ok(prefix)) => prefix <= 128,
+        _ => false,
+    }
+}
+
+/// Finds the 1-based line of a nested key by looking for each segment in turn.
+/// It's a textual search, good enough to point users at the right spot.
+fn line_of(source: &str, key_path: &[&str]) -> Option<usize> {
+    let mut offset = 0;
+    for key in key_path {
+        let needle = format!("\"{}\"", key);
+        offset += source[offset..].find(&needle)?;
+    }
+    Some(source[..offset].matches('\n').count() + 1)
+}
@@ -2,6 +2,7 @@ use chrono::Utc;
 use once_cell::sync::Lazy;
 use std::fs::File;
 use std::io::Write;
+use std::sync::atomic::{AtomicU8, Ordering};
 use std::sync::{Arc, Mutex};
 
 pub enum LogLevel {
@@ -49,10 +50,19 @@ impl LogLevel {
 }
 
 pub static LOG_FILE: Lazy<Arc<Mutex<Option<File>>>> = Lazy::new(|| Arc::new(Mutex::new(None)));
+static LOG_LEVEL: AtomicU8 = AtomicU8::new(1);
 use serde_json::Value;
 
+/// Sets the minimum level printed by `logger`.
+pub fn set_log_level(level_str: &str) {
+    LOG_LEVEL.store(LogLevel::from_str(level_str).index(), Ordering::Relaxed);
+}
+
 pub fn logger(level_str: &str, category: &str, message: &str, data: Option<&Value>) {
     let level = LogLevel::from_str(level_str);
+    if level.index() < LOG_LEVEL.load(Ordering::Relaxed) {
+        return;
+    }
     let (label, color_code) = level.details();
     let reset_color = "\x1b[0m";
     let time = Utc::now().format("%H:%M:%S.%3f").to_string();
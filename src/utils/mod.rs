@@ -14,6 +14,12 @@ mod check_for_updates;
 #[path = "logger.rs"]
 mod logger;
 
+#[path = "loadConfig.rs"]
+mod load_config;
+
+#[path = "reloadConfig.rs"]
+mod reload_config;
+
 #[path = "getVersion.rs"]
 mod version;
 
@@ -64,11 +70,13 @@ pub use check_for_updates::check_for_updates;
 pub use decode_track::decode_track;
 pub use encode_track::encode_track;
 pub use generate_random_letters::generate_random_letters;
-pub use get_stats::get_stats;
+pub use get_stats::{get_stats, update_statistics};
 pub use http1_make_request::http1_make_request;
 pub use internal_logger::init_logger;
 pub use load_hls::load_hls;
 pub use load_hls_playlist::load_hls_playlist;
+pub use load_config::load_config;
+pub use reload_config::reload_config;
 pub use logger::logger;
 pub use make_request::make_request;
 pub use parse_client::parse_client;
@@ -79,4 +87,4 @@ pub use validate_property::validate_property;
 pub use verify_discord_id::verify_discord_id;
 pub use verify_method::verify_method;
 pub use version::VersionResult;
-pub use version::get_version;
+pub use version::{get_version, get_version_object};
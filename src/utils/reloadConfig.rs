@@ -0,0 +1,73 @@
+use super::load_config::load_config;
+use super::logger::{logger, set_log_level};
+use super::types::audio_engine::AudioEngineCommand;
+use super::types::config::Config;
+use super::types::stats::RustlinkMock;
+use std::sync::{Arc, RwLock};
+use tokio::sync::mpsc;
+
+/// Reads the config file again and applies it without touching sessions or players.
+/// Returns the changed settings that only take effect after a restart.
+pub async fn reload_config(rustlink: &Arc<RwLock<RustlinkMock>>) -> Result<Vec<String>, String> {
+    let (path, audio_engine) = {
+        let rustlink_read = rustlink.read().unwrap();
+        (rustlink_read.config_path.clone(), rustlink_read.audio_engine.clone())
+    };
+    let config = load_config(&path)?;
+
+    let (tx, mut rx) = mpsc::channel(1);
+    let command = AudioEngineCommand::ReloadConfig {
+        config: Box::new(config.clone()),
+        resp: tx,
+    };
+    audio_engine.send(command).await.map_err(|e| e.to_string())?;
+    rx.recv().await.ok_or("No response from AudioEngine".to_string())?;
+
+    set_log_level(config.logging.level.as_deref().unwrap_or("info"));
+
+    let requires_restart = {
+        let mut rustlink_write = rustlink.write().unwrap();
+        rustlink_write.dos_protection.reload(&config);
+        rustlink_write.rate_limit.reload(&config);
+        let previous = &rustlink_write.config;
+        let requires_restart = requires_restart(previous, &config);
+
+        // Keep the values still in effect so later reloads keep reporting them
+        let mut config = config;
+        config.server.host = previous.server.host.clone();
+        config.server.port = previous.server.port;
+        config.logging.file = previous.logging.file.clone();
+        rustlink_write.config = config;
+        requires_restart
+    };
+
+    if requires_restart.is_empty() {
+        logger("info", "Config", &format!("Reloaded configuration from {}", path), None);
+    } else {
+        logger(
+            "warn",
+            "Config",
+            &format!(
+                "Reloaded configuration from {}, changes to {} require a restart",
+                path,
+                requires_restart.join(", ")
+            ),
+            None,
+        );
+    }
+    Ok(requires_restart)
+}
+
+fn requires_restart(previous: &Config, current: &Config) -> Vec<String> {
+    let mut changed = Vec::new();
+    if previous.server.host != current.server.host {
+        changed.push("server.host".to_string());
+    }
+    if previous.server.port != current.server.port {
+        changed.push("server.port".to_string());
+    }
+    if previous.logging.file != current.logging.file {
+        changed.push("logging.file".to_string());
+    }
+    changed
+}
@@ -0,0 +1,571 @@
+use std::future::Future;
+use std::sync::Arc;
+use std::sync::atomic::{AtomicBool, AtomicI64, Ordering};
+use std::time::Duration;
+use tokio::sync::{mpsc, oneshot};
+
+use crate::utils::logger;
+use super::gateway::{GatewayClosed, VoiceGateway, VoiceServerInfo};
+use super::udp::{VoiceUdp, SILENCE_FRAME};
+
+/// Encryption modes we can send with, most preferred first.
+pub const SUPPORTED_MODES: [&str; 2] = ["aead_aes256_gcm_rtpsize", "aead_xchacha20_poly1305_rtpsize"];
+
+// Frames of silence sent when playback stops
+const SILENCE_FRAMES: usize = 5;
+const SILENCE_SPACING: Duration = Duration::from_millis(20);
+// Attempts at a new session before giving up, waiting 1s, 2s, 4s... in between
+const RECONNECT_ATTEMPTS: u32 = 5;
+const RECONNECT_BACKOFF: Duration = Duration::from_secs(1);
+
+enum CloseAction {
+    Resume,
+    Reconnect,
+    Stop,
+}
+
+enum VoiceCommand {
+    Frame(Vec<u8>),
+    Stop,
+}
+
+/// Connection state shared between the player and its gateway task.
+struct VoiceStatus {
+    connected: AtomicBool,
+    // Set once the task gave up, after a close that can't be recovered from or failed reconnects
+    closed: AtomicBool,
+    // Milliseconds, -1 until the first heartbeat is acknowledged
+    ping: AtomicI64,
+}
+
+/// A player's voice connection. The gateway runs on its own task, which resumes or
+/// reconnects after drops and is shut down (closing the socket) when this is dropped.
+pub struct VoiceConnection {
+    pub info: VoiceServerInfo,
+    status: Arc<VoiceStatus>,
+    commands: mpsc::UnboundedSender<VoiceCommand>,
+    closed: mpsc::UnboundedReceiver<GatewayClosed>,
+    _shutdown: oneshot::Sender<()>,
+}
+
+impl VoiceConnection {
+    pub fn connect(info: VoiceServerInfo) -> Self {
+        let status = Arc::new(VoiceStatus {
+            connected: AtomicBool::new(false),
+            closed: AtomicBool::new(false),
+            ping: AtomicI64::new(-1),
+        });
+        let (commands, commands_rx) = mpsc::unbounded_channel();
+        let (closed_tx, closed) = mpsc::unbounded_channel();
+        let (shutdown, shutdown_rx) = oneshot::channel();
+        let task_status = status.clone();
+        let task_info = info.clone();
+        tokio::spawn(async move {
+            run(task_info, &task_status, closed_tx, commands_rx, shutdown_rx).await;
+            task_status.connected.store(false, Ordering::Relaxed);
+            task_status.closed.store(true, Ordering::Relaxed);
+        });
+        Self {
+            info,
+            status,
+            commands,
+            closed,
+            _shutdown: shutdown,
+        }
+    }
+
+    /// Next close Discord sent us since the last call, to report as `WebSocketClosedEvent`.
+    pub fn poll_closed(&mut self) -> Option<GatewayClosed> {
+        self.closed.try_recv().ok()
+    }
+
+    /// Queues an Opus frame for sending. Frames are dropped while not connected.
+    pub fn send_frame(&self, packet: &[u8]) -> bool {
+        self.is_connected() && self.commands.send(VoiceCommand::Frame(packet.to_vec())).is_ok()
+    }
+
+    /// Ends the current transmission with a few frames of silence and stops speaking.
+    pub fn stop(&self) {
+        let _ = self.commands.send(VoiceCommand::Stop);
+    }
+
+    pub fn is_connected(&self) -> bool {
+        self.status.connected.load(Ordering::Relaxed)
+    }
+
+    /// Whether the connection is gone for good and only a new voice state can bring it back.
+    pub fn is_closed(&self) -> bool {
+        self.status.closed.load(Ordering::Relaxed)
+    }
+
+    pub fn ping(&self) -> i64 {
+        self.status.ping.load(Ordering::Relaxed)
+    }
+}
+
+async fn run(
+    info: VoiceServerInfo,
+    status: &VoiceStatus,
+    closed_tx: mpsc::UnboundedSender<GatewayClosed>,
+    mut commands: mpsc::UnboundedReceiver<VoiceCommand>,
+    mut shutdown: oneshot::Receiver<()>,
+) {
+    let Some((mut gateway, mut udp)) = reconnect(&info, &mut shutdown).await else {
+        return;
+    };
+
+    loop {
+        status.connected.store(true, Ordering::Relaxed);
+        // Frames queued while disconnected are stale by now
+        while commands.try_recv().is_ok() {}
+
+        let Some(closed) = drive(&info, status, &mut gateway, &mut udp, &mut commands, &mut shutdown).await else {
+            return;
+        };
+        status.connected.store(false, Ordering::Relaxed);
+        status.ping.store(-1, Ordering::Relaxed);
+
+        let code = closed.code.map_or("none".to_string(), |c| c.to_string());
+        logger(
+            "warn",
+            "Voice",
+            &format!("Guild {}: gateway closed (code {}): {}", info.guild_id, code, closed.reason),
+            None,
+        );
+        let action = close_action(closed.code);
+        let _ = closed_tx.send(closed);
+
+        match action {
+            CloseAction::Stop => return,
+            CloseAction::Resume => match until_shutdown(&mut shutdown, VoiceGateway::resume(&info, gateway.seq_ack())).await {
+                None => return,
+                Some(Ok(resumed)) => {
+                    logger("debug", "Voice", &format!("Guild {}: session resumed", info.guild_id), None);
+                    gateway = resumed;
+                    continue;
+                }
+                Some(Err(e)) => {
+                    logger("debug", "Voice", &format!("Guild {}: resume failed, reconnecting: {}", info.guild_id, e), None);
+                }
+            },
+            CloseAction::Reconnect => {}
+        }
+
+        match reconnect(&info, &mut shutdown).await {
+            Some((new_gateway, new_udp)) => {
+                gateway = new_gateway;
+                udp = new_udp;
+            }
+            None => return,
+        }
+    }
+}
+
+/// Sends frames and keeps the gateway alive until it closes. `None` when the connection
+/// was dropped, after closing the socket.
+async fn drive(
+    info: &VoiceServerInfo,
+    status: &VoiceStatus,
+    gateway: &mut VoiceGateway,
+    udp: &mut VoiceUdp,
+    commands: &mut mpsc::UnboundedReceiver<VoiceCommand>,
+    shutdown: &mut oneshot::Receiver<()>,
+) -> Option<GatewayClosed> {
+    // Speaking is announced again after every (re)connect
+    let mut speaking = false;
+    // Silence frames left to close the transmission, sent 20ms apart like real audio
+    let mut silence_left = 0;
+    let silence = tokio::time::sleep(Duration::ZERO);
+    tokio::pin!(silence);
+    loop {
+        tokio::select! {
+            event = gateway.next_event() => match event {
+                Ok(_) => {
+                    if let Some(ping) = gateway.ping() {
+                        status.ping.store(ping as i64, Ordering::Relaxed);
+                    }
+                }
+                Err(closed) => return Some(closed),
+            },
+            Some(command) = commands.recv() => {
+                let result = match command {
+                    VoiceCommand::Frame(packet) => {
+                        // Audio coming back cuts the silence short, still speaking
+                        silence_left = 0;
+                        if !speaking {
+                            speaking = true;
+                            if let Err(e) = gateway.set_speaking(true, udp.ssrc()).await {
+                                logger("warn", "Voice", &format!("Guild {}: {}", info.guild_id, e), None);
+                            }
+                        }
+                        udp.send_frame(&packet).await
+                    }
+                    VoiceCommand::Stop => {
+                        if speaking && silence_left == 0 {
+                            silence_left = SILENCE_FRAMES;
+                            silence.as_mut().reset(tokio::time::Instant::now());
+                        }
+                        Ok(())
+                    }
+                };
+                if let Err(e) = result {
+                    logger("debug", "Voice", &format!("Guild {}: {}", info.guild_id, e), None);
+                }
+            }
+            () = &mut silence, if silence_left > 0 => {
+                silence_left -= 1;
+                let mut result = udp.send_frame(&SILENCE_FRAME).await;
+                if result.is_err() {
+                    silence_left = 0;
+                }
+                if silence_left == 0 {
+                    speaking = false;
+                    result = result.and(gateway.set_speaking(false, udp.ssrc()).await);
+                } else {
+                    let next = silence.deadline() + SILENCE_SPACING;
+                    silence.as_mut().reset(next);
+                }
+                if let Err(e) = result {
+                    logger("debug", "Voice", &format!("Guild {}: {}", info.guild_id, e), None);
+                }
+            }
+            _ = &mut *shutdown => {
+                gateway.close().await;
+                return None;
+            }
+        }
+    }
+}
+
+/// Decides how to recover from a close code.
+fn close_action(code: Option<u16>) -> CloseAction {
+    match code {
+        // Authentication failed, server not found, disconnected (kicked, channel deleted or
+        // moved, in which case the client sends a new voice state) and unknown encryption mode
+        Some(4004 | 4011 | 4014 | 4016) => CloseAction::Stop,
+        // Session no longer valid or timed out
+        Some(4006 | 4009) => CloseAction::Reconnect,
+        // Voice server crashed, or the socket dropped
+        _ => CloseAction::Resume,
+    }
+}
+
+/// Establishes a new session, retrying with exponential backoff. `None` when the
+/// connection was dropped or every attempt failed.
+async fn reconnect(info: &VoiceServerInfo, shutdown: &mut oneshot::Receiver<()>) -> Option<(VoiceGateway, VoiceUdp)> {
+    for attempt in 0..RECONNECT_ATTEMPTS {
+        if attempt > 0 {
+            until_shutdown(shutdown, tokio::time::sleep(RECONNECT_BACKOFF * 2u32.pow(attempt - 1))).await?;
+        }
+        match until_shutdown(shutdown, establish(info)).await? {
+            Ok(established) => return Some(established),
+            Err(e) => logger(
+                "warn",
+                "Voice",
+                &format!("Guild {}: connection attempt {}/{} failed: {}", info.guild_id, attempt + 1, RECONNECT_ATTEMPTS, e),
+                None,
+            ),
+        }
+    }
+    logger("error", "Voice", &format!("Guild {}: giving up on the voice connection", info.guild_id), None);
+    None
+}
+
+/// Runs the gateway handshake and IP discovery through to the session description.
+async fn establish(info: &VoiceServerInfo) -> Result<(VoiceGateway, VoiceUdp), String> {
+    let (mut gateway, ready) = VoiceGateway::connect(info).await?;
+
+    let mode = SUPPORTED_MODES
+        .iter()
+        .find(|mode| ready.modes.iter().any(|m| m == *mode))
+        .ok_or_else(|| format!("No supported encryption mode in {:?}", ready.modes))?;
+
+    let mut udp = VoiceUdp::connect(&ready.ip, ready.port, ready.ssrc).await?;
+    let (address, port) = udp.discover().await?;
+
+    let session = gateway.select_protocol(&address, port, mode).await?;
+    udp.set_session(&session.mode, &session.secret_key)?;
+    logger(
+        "debug",
+        "Voice",
+        &format!("Guild {}: connected to {} (ssrc {}, {})", info.guild_id, info.endpoint, ready.ssrc, session.mode),
+        None,
+    );
+    Ok((gateway, udp))
+}
+
+/// Runs `future` unless the connection is dropped first.
+async fn until_shutdown<T>(shutdown: &mut oneshot::Receiver<()>, future: impl Future<Output = T>) -> Option<T> {
+    tokio::select! {
+        value = future => Some(value),
+        _ = shutdown => None,
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::playback::decoder::StreamInput;
+    use crate::playback::player::Player;
+    use crate::types::config::AudioConfig;
+    use crate::voice::gateway::op;
+    use futures_util::{SinkExt, StreamExt};
+    use serde_json::{json, Value};
+    use tokio::net::{TcpListener, UdpSocket};
+    use tokio_tungstenite::tungstenite::Message;
+    use tokio_tungstenite::tungstenite::protocol::CloseFrame;
+    use tokio_tungstenite::tungstenite::protocol::frame::coding::CloseCode;
+
+    const SSRC: u32 = 1234;
+    const SECRET_KEY: [u8; 32] = [7; 32];
+
+    /// Answers IP discovery like a voice server, reporting the sender's own address, and
+    /// forwards every other packet it receives.
+    async fn mock_udp() -> (u16, mpsc::UnboundedReceiver<Vec<u8>>) {
+        let socket = UdpSocket::bind("127.0.0.1:0").await.unwrap();
+        let port = socket.local_addr().unwrap().port();
+        let (packets_tx, packets) = mpsc::unbounded_channel();
+        tokio::spawn(async move {
+            let mut request = [0u8; 1500];
+            while let Ok((length, from)) = socket.recv_from(&mut request).await {
+                if request[0..2] != 1u16.to_be_bytes() {
+                    let _ = packets_tx.send(request[..length].to_vec());
+                    continue;
+                }
+                let mut response = [0u8; 74];
+                response[0..2].copy_from_slice(&2u16.to_be_bytes());
+                response[2..4].copy_from_slice(&70u16.to_be_bytes());
+                response[4..8].copy_from_slice(&request[4..8]);
+                let address = from.ip().to_string();
+                response[8..8 + address.len()].copy_from_slice(address.as_bytes());
+                response[72..74].copy_from_slice(&from.port().to_be_bytes());
+                let _ = socket.send_to(&response, from).await;
+            }
+        });
+        (port, packets)
+    }
+
+    /// Voice gateway that runs the handshake offering `mode` and acknowledges heartbeats,
+    /// forwarding the payload of every op it receives to `received`. With `close_code` it
+    /// closes the socket right after the session description instead.
+    async fn mock_gateway(
+        udp_port: u16,
+        mode: &'static str,
+        received: mpsc::UnboundedSender<(u8, Value)>,
+        close_code: Option<u16>,
+    ) -> String {
+        let listener = TcpListener::bind("127.0.0.1:0").await.unwrap();
+        let address = listener.local_addr().unwrap();
+        tokio::spawn(async move {
+            let (stream, _) = listener.accept().await.unwrap();
+            let mut socket = tokio_tungstenite::accept_async(stream).await.unwrap();
+            let send = |op: u8, d: Value| Message::Text(json!({ "op": op, "d": d }).to_string());
+
+            socket.send(send(op::HELLO, json!({ "heartbeat_interval": 50.0 }))).await.unwrap();
+            while let Some(Ok(Message::Text(text))) = socket.next().await {
+                let payload: Value = serde_json::from_str(&text).unwrap();
+                let op = payload["op"].as_u64().unwrap() as u8;
+                let data = payload["d"].clone();
+                let reply = match op {
+                    op::IDENTIFY => Some(send(op::READY, json!({
+                        "ssrc": SSRC,
+                        "ip": "127.0.0.1",
+                        "port": udp_port,
+                        "modes": ["xsalsa20_poly1305", mode]
+                    }))),
+                    op::SELECT_PROTOCOL => Some(send(op::SESSION_DESCRIPTION, json!({
+                        "mode": data["data"]["mode"],
+                        "secret_key": SECRET_KEY
+                    }))),
+                    op::HEARTBEAT => Some(send(op::HEARTBEAT_ACK, json!({ "t": data["t"] }))),
+                    _ => None,
+                };
+                let _ = received.send((op, data));
+                if let Some(reply) = reply {
+                    socket.send(reply).await.unwrap();
+                }
+                if let (op::SELECT_PROTOCOL, Some(code)) = (op, close_code) {
+                    let frame = CloseFrame { code: CloseCode::from(code), reason: "Disconnected".into() };
+                    let _ = socket.close(Some(frame)).await;
+                    return;
+                }
+            }
+        });
+        format!("ws://{}", address)
+    }
+
+    #[tokio::test]
+    async fn connects_to_mock_gateway_and_reports_ping() {
+        let (received_tx, mut received) = mpsc::unbounded_channel();
+        let endpoint = mock_gateway(mock_udp().await.0, SUPPORTED_MODES[0], received_tx, None).await;
+        let (events, _events) = mpsc::unbounded_channel();
+        let mut player = Player::new(
+            "session".to_string(),
+            "111".to_string(),
+            "222".to_string(),
+            events,
+            AudioConfig::default(),
+        );
+        player.update_voice("voice-session", "token", &endpoint);
+
+        let (op, identify) = received.recv().await.unwrap();
+        assert_eq!(op, op::IDENTIFY);
+        assert_eq!(identify["server_id"], "111");
+        assert_eq!(identify["user_id"], "222");
+        assert_eq!(identify["session_id"], "voice-session");
+        assert_eq!(identify["token"], "token");
+
+        let (op, select) = received.recv().await.unwrap();
+        assert_eq!(op, op::SELECT_PROTOCOL);
+        assert_eq!(select["protocol"], "udp");
+        assert_eq!(select["data"]["address"], "127.0.0.1");
+        assert_eq!(select["data"]["mode"], SUPPORTED_MODES[0]);
+
+        let state = tokio::time::timeout(Duration::from_secs(5), async {
+            loop {
+                let state = player.to_json()["state"].clone();
+                if state["ping"].as_i64().unwrap() >= 0 {
+                    return state;
+                }
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("ping was never reported");
+        assert_eq!(state["connected"], true);
+        assert!(state["ping"].as_i64().unwrap() < 1000);
+        assert_eq!(player.to_json()["voice"]["endpoint"], endpoint);
+    }
+
+    #[tokio::test]
+    async fn drops_the_track_once_the_connection_is_closed_for_good() {
+        let (received_tx, _received) = mpsc::unbounded_channel();
+        let endpoint = mock_gateway(mock_udp().await.0, SUPPORTED_MODES[0], received_tx, Some(4014)).await;
+        let (events_tx, mut events) = mpsc::unbounded_channel();
+        let mut player = Player::new(
+            "session".to_string(),
+            "111".to_string(),
+            "222".to_string(),
+            events_tx,
+            AudioConfig::default(),
+        );
+        player.update_voice("voice-session", "token", &endpoint);
+        let fixture = std::path::Path::new(env!("CARGO_MANIFEST_DIR")).join("tests/fixtures/sine.wav");
+        let stream = async move { Ok(StreamInput::File(fixture)) };
+        assert!(player.play("encoded".to_string(), json!({ "title": "sine" }), json!({}), false, stream));
+
+        tokio::time::timeout(Duration::from_secs(5), async {
+            while !player.voice.as_ref().unwrap().is_closed() {
+                tokio::time::sleep(Duration::from_millis(10)).await;
+            }
+        })
+        .await
+        .expect("connection never closed");
+
+        // Holding until the client had its chance to send a new voice state
+        for _ in 0..499 {
+            player.tick();
+        }
+        assert!(player.track.is_some());
+        player.tick();
+        assert!(player.track.is_none());
+
+        let mut types = Vec::new();
+        while let Ok(event) = events.try_recv() {
+            if event.payload["type"] == "WebSocketClosedEvent" {
+                assert_eq!(event.payload["code"], 4014);
+                assert_eq!(event.payload["byRemote"], true);
+            }
+            if event.payload["type"] == "TrackEndEvent" {
+                assert_eq!(event.payload["reason"], "cleanup");
+            }
+            types.push(event.payload["type"].as_str().unwrap().to_string());
+        }
+        assert_eq!(types, ["TrackStartEvent", "WebSocketClosedEvent", "TrackStuckEvent", "TrackEndEvent"]);
+
+        // The same voice state connects again
+        player.update_voice("voice-session", "token", &endpoint);
+        assert!(!player.voice.as_ref().unwrap().is_closed());
+    }
+
+    /// Splits an rtpsize packet into its RTP header and decrypted Opus frame.
+    fn decrypt(mode: &str, packet: &[u8]) -> ([u8; 12], Vec<u8>) {
+        use aes_gcm::Aes256Gcm;
+        use aes_gcm::aead::{Aead, KeyInit, Payload};
+        use chacha20poly1305::XChaCha20Poly1305;
+
+        let header: [u8; 12] = packet[..12].try_into().unwrap();
+        let (encrypted, counter) = packet[12..].split_at(packet.len() - 16);
+        let payload = Payload { msg: encrypted, aad: &header };
+        let decrypted = if mode == "aead_aes256_gcm_rtpsize" {
+            let mut nonce = [0u8; 12];
+            nonce[..4].copy_from_slice(counter);
+            Aes256Gcm::new(&SECRET_KEY.into()).decrypt(&nonce.into(), payload)
+        } else {
+            let mut nonce = [0u8; 24];
+            nonce[..4].copy_from_slice(counter);
+            XChaCha20Poly1305::new(&SECRET_KEY.into()).decrypt(&nonce.into(), payload)
+        };
+        (header, decrypted.expect("packet doesn't decrypt with the session key"))
+    }
+
+    #[tokio::test]
+    async fn sends_rtp_and_closes_with_silence() {
+        for mode in SUPPORTED_MODES {
+            let (udp_port, mut packets) = mock_udp().await;
+            let (received_tx, mut received) = mpsc::unbounded_channel();
+            let endpoint = mock_gateway(udp_port, mode, received_tx, None).await;
+            let connection = VoiceConnection::connect(VoiceServerInfo {
+                guild_id: "111".to_string(),
+                user_id: "222".to_string(),
+                session_id: "voice-session".to_string(),
+                token: "token".to_string(),
+                endpoint,
+            });
+            tokio::time::timeout(Duration::from_secs(5), async {
+                while !connection.is_connected() {
+                    tokio::time::sleep(Duration::from_millis(10)).await;
+                }
+            })
+            .await
+            .expect("never connected");
+
+            let frames: Vec<Vec<u8>> = (0..3u8).map(|i| vec![0xFC, i, i]).collect();
+            for frame in &frames {
+                assert!(connection.send_frame(frame));
+            }
+            connection.stop();
+
+            let mut sent = Vec::new();
+            for _ in 0..frames.len() + SILENCE_FRAMES {
+                let packet = tokio::time::timeout(Duration::from_secs(1), packets.recv()).await.expect("packet missing");
+                sent.push(decrypt(mode, &packet.unwrap()));
+            }
+            let extra = tokio::time::timeout(Duration::from_millis(100), packets.recv()).await;
+            assert!(extra.is_err(), "{}: more than {} silence frames", mode, SILENCE_FRAMES);
+
+            let (first, _) = sent[0];
+            for (i, (header, payload)) in sent.iter().enumerate() {
+                assert_eq!(header[..2], [0x80, 0x78], "{}", mode);
+                let sequence = u16::from_be_bytes([header[2], header[3]]);
+                assert_eq!(sequence, u16::from_be_bytes([first[2], first[3]]).wrapping_add(i as u16), "{}", mode);
+                let timestamp = u32::from_be_bytes(header[4..8].try_into().unwrap());
+                let expected = u32::from_be_bytes(first[4..8].try_into().unwrap()).wrapping_add(960 * i as u32);
+                assert_eq!(timestamp, expected, "{}", mode);
+                assert_eq!(header[8..12], SSRC.to_be_bytes(), "{}", mode);
+                let expected: &[u8] = frames.get(i).map_or(&SILENCE_FRAME, |frame| frame);
+                assert_eq!(payload, expected, "{}", mode);
+            }
+
+            // Speaking for as long as audio, silence included, was going out
+            let mut speaking = Vec::new();
+            while let Ok((op, data)) = received.try_recv() {
+                if op == op::SPEAKING {
+                    assert_eq!(data["ssrc"], SSRC);
+                    speaking.push(data["speaking"].as_u64().unwrap());
+                }
+            }
+            assert_eq!(speaking, [1, 0], "{}", mode);
+        }
+    }
+}
@@ -0,0 +1,286 @@
+use futures_util::{SinkExt, StreamExt};
+use serde_json::{json, Value};
+use std::time::{Duration, SystemTime, UNIX_EPOCH};
+use tokio::net::TcpStream;
+use tokio::time::{timeout, Instant};
+use tokio_tungstenite::tungstenite::Message;
+use tokio_tungstenite::{connect_async, MaybeTlsStream, WebSocketStream};
+
+const GATEWAY_VERSION: u8 = 8;
+// How long each handshake step may take before the connection is abandoned
+const HANDSHAKE_TIMEOUT: Duration = Duration::from_secs(10);
+
+pub mod op {
+    pub const IDENTIFY: u8 = 0;
+    pub const SELECT_PROTOCOL: u8 = 1;
+    pub const READY: u8 = 2;
+    pub const HEARTBEAT: u8 = 3;
+    pub const SESSION_DESCRIPTION: u8 = 4;
+    pub const SPEAKING: u8 = 5;
+    pub const HEARTBEAT_ACK: u8 = 6;
+    pub const RESUME: u8 = 7;
+    pub const HELLO: u8 = 8;
+    pub const RESUMED: u8 = 9;
+}
+
+/// What the `voice` object of a player update hands us, plus who we are.
+#[derive(Debug, Clone, PartialEq)]
+pub struct VoiceServerInfo {
+    pub guild_id: String,
+    pub user_id: String,
+    pub session_id: String,
+    pub token: String,
+    pub endpoint: String,
+}
+
+#[derive(Debug, Clone)]
+pub struct Ready {
+    pub ssrc: u32,
+    pub ip: String,
+    pub port: u16,
+    pub modes: Vec<String>,
+}
+
+#[derive(Debug, Clone)]
+pub struct SessionDescription {
+    pub mode: String,
+    pub secret_key: Vec<u8>,
+}
+
+/// Why the gateway stopped: the close frame's code and reason, or `None` for a dropped socket.
+#[derive(Debug, Clone)]
+pub struct GatewayClosed {
+    pub code: Option<u16>,
+    pub reason: String,
+    /// Whether the voice server ended the connection, rather than us after a failed send or read.
+    pub by_remote: bool,
+}
+
+/// Discord voice WebSocket. `connect` runs the handshake up to Ready, `select_protocol`
+/// finishes it, and `next_event` keeps the heartbeat going afterwards. `resume` picks a
+/// dropped session back up without a new handshake.
+pub struct VoiceGateway {
+    socket: WebSocketStream<MaybeTlsStream<TcpStream>>,
+    heartbeat_interval: Duration,
+    next_heartbeat: Instant,
+    // Last sequence number received, acknowledged in heartbeats
+    seq_ack: i64,
+    ping: Option<u64>,
+}
+
+impl VoiceGateway {
+    pub async fn connect(info: &VoiceServerInfo) -> Result<(Self, Ready), String> {
+        let mut gateway = Self::open(info, -1).await?;
+        gateway
+            .send(op::IDENTIFY, json!({
+                "server_id": info.guild_id,
+                "user_id": info.user_id,
+                "session_id": info.session_id,
+                "token": info.token,
+            }))
+            .await?;
+
+        let ready = gateway.expect(op::READY).await?;
+        let ready = Ready {
+            ssrc: ready["ssrc"].as_u64().ok_or("Ready is missing ssrc")? as u32,
+            ip: ready["ip"].as_str().ok_or("Ready is missing ip")?.to_string(),
+            port: ready["port"].as_u64().ok_or("Ready is missing port")? as u16,
+            modes: ready["modes"]
+                .as_array()
+                .map(|modes| modes.iter().filter_map(|m| m.as_str().map(String::from)).collect())
+                .unwrap_or_default(),
+        };
+        Ok((gateway, ready))
+    }
+
+    /// Resumes the session of a closed gateway; the UDP side and its key stay valid.
+    pub async fn resume(info: &VoiceServerInfo, seq_ack: i64) -> Result<Self, String> {
+        let mut gateway = Self::open(info, seq_ack).await?;
+        gateway
+            .send(op::RESUME, json!({
+                "server_id": info.guild_id,
+                "session_id": info.session_id,
+                "token": info.token,
+                "seq_ack": seq_ack,
+            }))
+            .await?;
+        gateway.expect(op::RESUMED).await?;
+        Ok(gateway)
+    }
+
+    /// Connects and waits for Hello.
+    async fn open(info: &VoiceServerInfo, seq_ack: i64) -> Result<Self, String> {
+        let (socket, _) = timeout(HANDSHAKE_TIMEOUT, connect_async(gateway_url(&info.endpoint)))
+            .await
+            .map_err(|_| "Timed out connecting to the voice gateway".to_string())?
+            .map_err(|e| format!("Failed to connect to the voice gateway: {}", e))?;
+
+        let mut gateway = Self {
+            socket,
+            heartbeat_interval: Duration::ZERO,
+            next_heartbeat: Instant::now(),
+            seq_ack,
+            ping: None,
+        };
+
+        let hello = gateway.expect(op::HELLO).await?;
+        let interval = hello["heartbeat_interval"]
+            .as_f64()
+            .ok_or("Hello is missing heartbeat_interval")?;
+        gateway.heartbeat_interval = Duration::from_millis(interval as u64);
+        gateway.next_heartbeat = Instant::now() + gateway.heartbeat_interval;
+        Ok(gateway)
+    }
+
+    /// Tells Discord where to send UDP and which encryption mode to use.
+    pub async fn select_protocol(&mut self, address: &str, port: u16, mode: &str) -> Result<SessionDescription, String> {
+        self.send(op::SELECT_PROTOCOL, json!({
+            "protocol": "udp",
+            "data": { "address": address, "port": port, "mode": mode }
+        }))
+        .await?;
+
+        let description = self.expect(op::SESSION_DESCRIPTION).await?;
+        let secret_key = description["secret_key"]
+            .as_array()
+            .ok_or("Session description is missing secret_key")?
+            .iter()
+            .map(|b| b.as_u64().map(|b| b as u8))
+            .collect::<Option<Vec<u8>>>()
+            .ok_or("Session description has an invalid secret_key")?;
+        Ok(SessionDescription {
+            mode: description["mode"].as_str().unwrap_or(mode).to_string(),
+            secret_key,
+        })
+    }
+
+    pub async fn set_speaking(&mut self, speaking: bool, ssrc: u32) -> Result<(), String> {
+        self.send(op::SPEAKING, json!({
+            // 1 is the microphone flag
+            "speaking": if speaking { 1 } else { 0 },
+            "delay": 0,
+            "ssrc": ssrc
+        }))
+        .await
+    }
+
+    pub fn seq_ack(&self) -> i64 {
+        self.seq_ack
+    }
+
+    /// Round trip of the last acknowledged heartbeat in milliseconds.
+    pub fn ping(&self) -> Option<u64> {
+        self.ping
+    }
+
+    /// Sends heartbeats and handles incoming ops until the socket closes.
+    /// Returns after every received op so callers can pick up the new ping.
+    pub async fn next_event(&mut self) -> Result<(u8, Value), GatewayClosed> {
+        loop {
+            tokio::select! {
+                // No heartbeats until Hello has told us the interval
+                _ = tokio::time::sleep_until(self.next_heartbeat), if !self.heartbeat_interval.is_zero() => {
+                    self.next_heartbeat += self.heartbeat_interval;
+                    let heartbeat = json!({ "t": now_millis(), "seq_ack": self.seq_ack });
+                    if let Err(e) = self.send(op::HEARTBEAT, heartbeat).await {
+                        return Err(GatewayClosed { code: None, reason: e, by_remote: false });
+                    }
+                }
+                message = self.socket.next() => {
+                    let (op, data) = match self.receive(message) {
+                        Ok(Some(event)) => event,
+                        Ok(None) => continue,
+                        Err(closed) => return Err(closed),
+                    };
+                    if op == op::HEARTBEAT_ACK
+                        && let Some(nonce) = data["t"].as_u64()
+                    {
+                        self.ping = Some(now_millis().saturating_sub(nonce));
+                    }
+                    return Ok((op, data));
+                }
+            }
+        }
+    }
+
+    pub async fn close(&mut self) {
+        let _ = self.socket.close(None).await;
+    }
+
+    /// Waits for `expected`, answering heartbeats due in the meantime.
+    async fn expect(&mut self, expected: u8) -> Result<Value, String> {
+        let deadline = Instant::now() + HANDSHAKE_TIMEOUT;
+        loop {
+            let event = tokio::time::timeout_at(deadline, self.next_event())
+                .await
+                .map_err(|_| format!("Timed out waiting for voice op {}", expected))?;
+            match event {
+                Ok((op, data)) if op == expected => return Ok(data),
+                Ok(_) => continue,
+                Err(closed) => {
+                    return Err(match closed.code {
+                        Some(code) => format!("Voice gateway closed with {} ({})", code, closed.reason),
+                        None => format!("Voice gateway connection lost: {}", closed.reason),
+                    });
+                }
+            }
+        }
+    }
+
+    async fn send(&mut self, op: u8, data: Value) -> Result<(), String> {
+        let payload = json!({ "op": op, "d": data }).to_string();
+        self.socket
+            .send(Message::Text(payload))
+            .await
+            .map_err(|e| format!("Failed to send voice op {}: {}", op, e))
+    }
+
+    fn receive(
+        &mut self,
+        message: Option<Result<Message, tokio_tungstenite::tungstenite::Error>>,
+    ) -> Result<Option<(u8, Value)>, GatewayClosed> {
+        let text = match message {
+            Some(Ok(Message::Text(text))) => text,
+            Some(Ok(Message::Close(frame))) => {
+                return Err(GatewayClosed {
+                    code: frame.as_ref().map(|f| u16::from(f.code)),
+                    reason: frame.map(|f| f.reason.to_string()).unwrap_or_default(),
+                    by_remote: true,
+                });
+            }
+            Some(Ok(_)) => return Ok(None),
+            Some(Err(e)) => return Err(GatewayClosed { code: None, reason: e.to_string(), by_remote: false }),
+            None => {
+                return Err(GatewayClosed {
+                    code: None,
+                    reason: "Connection closed".to_string(),
+                    by_remote: true,
+                });
+            }
+        };
+
+        let Ok(payload) = serde_json::from_str::<Value>(&text) else {
+            return Ok(None);
+        };
+        if let Some(seq) = payload["seq"].as_i64() {
+            self.seq_ack = seq;
+        }
+        let Some(op) = payload["op"].as_u64() else {
+            return Ok(None);
+        };
+        Ok(Some((op as u8, payload["d"].clone())))
+    }
+}
+
+/// Endpoints come without a scheme (`region.discord.media:443`); a full `ws://` URL is
+/// used as is, which is how a local mock server is reached.
+fn gateway_url(endpoint: &str) -> String {
+    if endpoint.starts_with("ws://") || endpoint.starts_with("wss://") {
+        return endpoint.to_string();
+    }
+    format!("wss://{}/?v={}", endpoint.trim_end_matches('/'), GATEWAY_VERSION)
+}
+
+fn now_millis() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).unwrap_or_default().as_millis() as u64
+}
@@ -0,0 +1,3 @@
+pub mod connection;
+pub mod gateway;
+pub mod udp;
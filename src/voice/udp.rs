@@ -0,0 +1,156 @@
+use aes_gcm::aead::{Aead, KeyInit, Payload};
+use aes_gcm::Aes256Gcm;
+use chacha20poly1305::XChaCha20Poly1305;
+use std::time::Duration;
+use tokio::net::UdpSocket;
+use tokio::time::timeout;
+
+use crate::playback::opus::FRAME_SIZE;
+
+const RTP_HEADER_LEN: usize = 12;
+// Version 2, no padding, extension or CSRCs
+const RTP_VERSION: u8 = 0x80;
+// Dynamic payload type Discord uses for Opus
+const RTP_PAYLOAD_TYPE: u8 = 0x78;
+const DISCOVERY_PACKET_LEN: usize = 74;
+const DISCOVERY_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Opus encoding of 20ms of silence, sent after playback stops so clients don't
+/// interpolate the last real frame.
+pub const SILENCE_FRAME: [u8; 3] = [0xF8, 0xFF, 0xFE];
+
+enum Cipher {
+    Aes256Gcm(Box<Aes256Gcm>),
+    XChaCha20Poly1305(Box<XChaCha20Poly1305>),
+}
+
+impl Cipher {
+    fn new(mode: &str, key: &[u8]) -> Result<Self, String> {
+        let invalid_key = |_| format!("Invalid secret key for {}", mode);
+        match mode {
+            "aead_aes256_gcm_rtpsize" => Ok(Cipher::Aes256Gcm(Box::new(
+                Aes256Gcm::new_from_slice(key).map_err(invalid_key)?,
+            ))),
+            "aead_xchacha20_poly1305_rtpsize" => Ok(Cipher::XChaCha20Poly1305(Box::new(
+                XChaCha20Poly1305::new_from_slice(key).map_err(invalid_key)?,
+            ))),
+            _ => Err(format!("Unsupported encryption mode {}", mode)),
+        }
+    }
+
+    /// The rtpsize modes authenticate the RTP header and take a 32-bit counter as the
+    /// start of an otherwise zeroed nonce.
+    fn encrypt(&self, header: &[u8], payload: &[u8], counter: u32) -> Result<Vec<u8>, String> {
+        let payload = Payload { msg: payload, aad: header };
+        let result = match self {
+            Cipher::Aes256Gcm(cipher) => {
+                let mut nonce = [0u8; 12];
+                nonce[..4].copy_from_slice(&counter.to_be_bytes());
+                cipher.encrypt(&nonce.into(), payload)
+            }
+            Cipher::XChaCha20Poly1305(cipher) => {
+                let mut nonce = [0u8; 24];
+                nonce[..4].copy_from_slice(&counter.to_be_bytes());
+                cipher.encrypt(&nonce.into(), payload)
+            }
+        };
+        result.map_err(|_| "Failed to encrypt voice packet".to_string())
+    }
+}
+
+/// UDP side of a voice connection: sends encrypted Opus frames as RTP packets.
+pub struct VoiceUdp {
+    socket: UdpSocket,
+    ssrc: u32,
+    cipher: Option<Cipher>,
+    sequence: u16,
+    timestamp: u32,
+    nonce: u32,
+}
+
+impl VoiceUdp {
+    /// Binds a socket to the voice server at `ip:port`. Frames can only be sent once
+    /// `set_session` has provided the key.
+    pub async fn connect(ip: &str, port: u16, ssrc: u32) -> Result<Self, String> {
+        let socket = UdpSocket::bind("0.0.0.0:0")
+            .await
+            .map_err(|e| format!("Failed to bind UDP socket: {}", e))?;
+        socket
+            .connect((ip, port))
+            .await
+            .map_err(|e| format!("Failed to reach voice server {}:{}: {}", ip, port, e))?;
+        Ok(Self {
+            socket,
+            ssrc,
+            cipher: None,
+            sequence: rand::random(),
+            timestamp: rand::random(),
+            nonce: 0,
+        })
+    }
+
+    /// Asks the voice server which address and port our packets arrive from.
+    pub async fn discover(&self) -> Result<(String, u16), String> {
+        let mut request = [0u8; DISCOVERY_PACKET_LEN];
+        request[0..2].copy_from_slice(&1u16.to_be_bytes());
+        request[2..4].copy_from_slice(&70u16.to_be_bytes());
+        request[4..8].copy_from_slice(&self.ssrc.to_be_bytes());
+        self.socket
+            .send(&request)
+            .await
+            .map_err(|e| format!("Failed to send IP discovery: {}", e))?;
+
+        let mut response = [0u8; DISCOVERY_PACKET_LEN];
+        let received = timeout(DISCOVERY_TIMEOUT, self.socket.recv(&mut response))
+            .await
+            .map_err(|_| "Timed out waiting for IP discovery".to_string())?
+            .map_err(|e| format!("Failed to receive IP discovery: {}", e))?;
+        if received < DISCOVERY_PACKET_LEN || response[0..2] != 2u16.to_be_bytes() {
+            return Err("Invalid IP discovery response".to_string());
+        }
+
+        let address = &response[8..72];
+        let end = address.iter().position(|b| *b == 0).unwrap_or(address.len());
+        let address = String::from_utf8(address[..end].to_vec())
+            .map_err(|_| "Invalid address in IP discovery response".to_string())?;
+        let port = u16::from_be_bytes([response[72], response[73]]);
+        Ok((address, port))
+    }
+
+    pub fn set_session(&mut self, mode: &str, secret_key: &[u8]) -> Result<(), String> {
+        self.cipher = Some(Cipher::new(mode, secret_key)?);
+        Ok(())
+    }
+
+    pub fn ssrc(&self) -> u32 {
+        self.ssrc
+    }
+
+    /// Wraps one 20ms Opus frame in an encrypted RTP packet and sends it.
+    pub async fn send_frame(&mut self, opus: &[u8]) -> Result<(), String> {
+        let cipher = self.cipher.as_ref().ok_or("Voice session isn't ready")?;
+
+        let mut header = [0u8; RTP_HEADER_LEN];
+        header[0] = RTP_VERSION;
+        header[1] = RTP_PAYLOAD_TYPE;
+        header[2..4].copy_from_slice(&self.sequence.to_be_bytes());
+        header[4..8].copy_from_slice(&self.timestamp.to_be_bytes());
+        header[8..12].copy_from_slice(&self.ssrc.to_be_bytes());
+
+        let encrypted = cipher.encrypt(&header, opus, self.nonce)?;
+        let mut packet = Vec::with_capacity(RTP_HEADER_LEN + encrypted.len() + 4);
+        packet.extend_from_slice(&header);
+        packet.extend_from_slice(&encrypted);
+        packet.extend_from_slice(&self.nonce.to_be_bytes());
+
+        self.sequence = self.sequence.wrapping_add(1);
+        self.timestamp = self.timestamp.wrapping_add(FRAME_SIZE as u32);
+        self.nonce = self.nonce.wrapping_add(1);
+
+        self.socket
+            .send(&packet)
+            .await
+            .map(|_| ())
+            .map_err(|e| format!("Failed to send voice packet: {}", e))
+    }
+}